@@ -0,0 +1,148 @@
+//   Various functions for manipulating arrays; used by the exchange to merge the calldata of matched orders.
+//   Calldata is SCALE encoded ink! calldata: a 4 byte message selector followed by the encoded arguments.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+/// Length of the message selector prefixing ink! calldata.
+pub const SELECTOR_LEN: usize = 4;
+
+/// Replace bytes in an array with bytes in another array, guarded by a bitmask.
+///dev Panics unless `array`, `desired` and `mask` have the same length
+///param array The original array
+///param desired The target array
+///param mask The mask specifying which bits can be changed
+pub fn guarded_array_replace(array: &mut [u8], desired: &[u8], mask: &[u8]) {
+    assert_eq!(array.len(), desired.len(), "desired length mismatch");
+    assert_eq!(array.len(), mask.len(), "mask length mismatch");
+    for ((byte, desired), mask) in array.iter_mut().zip(desired).zip(mask) {
+        *byte = (*byte & !mask) | (desired & mask);
+    }
+}
+
+/// Return whether or not two orders' calldata specifications can match, merging them in place.
+///dev Each side's replacement pattern lets the counterparty fill in the masked bits of its calldata.
+///    The selector is never replaceable, so a pattern touching it cannot match.
+///param buy_calldata Buy-side order calldata, replaced according to `buy_replacement_pattern`
+///param buy_replacement_pattern Buy-side order calldata replacement mask, empty for no replacement
+///param sell_calldata Sell-side order calldata, replaced according to `sell_replacement_pattern`
+///param sell_replacement_pattern Sell-side order calldata replacement mask, empty for no replacement
+///return Whether or not the merged calldata of both sides is identical
+pub fn order_calldata_can_match(
+    buy_calldata: &mut [u8],
+    buy_replacement_pattern: &[u8],
+    sell_calldata: &mut [u8],
+    sell_replacement_pattern: &[u8],
+) -> bool {
+    // Both sides must call a message of the same shape.
+    if buy_calldata.len() < SELECTOR_LEN || buy_calldata.len() != sell_calldata.len() {
+        return false
+    }
+    if !pattern_can_apply(buy_replacement_pattern, buy_calldata.len())
+        || !pattern_can_apply(sell_replacement_pattern, sell_calldata.len())
+    {
+        return false
+    }
+    if !buy_replacement_pattern.is_empty() {
+        guarded_array_replace(buy_calldata, sell_calldata, buy_replacement_pattern);
+    }
+    if !sell_replacement_pattern.is_empty() {
+        guarded_array_replace(sell_calldata, buy_calldata, sell_replacement_pattern);
+    }
+    buy_calldata == sell_calldata
+}
+
+/// Whether `pattern` is empty, or covers calldata of length `len` and leaves its selector untouched.
+fn pattern_can_apply(pattern: &[u8], len: usize) -> bool {
+    pattern.is_empty()
+        || (pattern.len() == len && pattern[..SELECTOR_LEN].iter().all(|byte| *byte == 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guarded_array_replace_only_changes_masked_bits() {
+        let mut array = [0b1010_1010, 0xff, 0x00];
+        guarded_array_replace(&mut array, &[0b0101_0101, 0x00, 0xff], &[0b0000_1111, 0x00, 0xf0]);
+        assert_eq!(array, [0b1010_0101, 0xff, 0xf0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "desired length mismatch")]
+    fn guarded_array_replace_rejects_desired_length_mismatch() {
+        guarded_array_replace(&mut [0; 3], &[0; 2], &[0; 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "mask length mismatch")]
+    fn guarded_array_replace_rejects_mask_length_mismatch() {
+        guarded_array_replace(&mut [0; 3], &[0; 3], &[0; 4]);
+    }
+
+    #[test]
+    fn identical_calldata_matches() {
+        let mut buy = [1, 2, 3, 4, 5];
+        let mut sell = [1, 2, 3, 4, 5];
+        assert!(order_calldata_can_match(&mut buy, &[], &mut sell, &[]));
+    }
+
+    #[test]
+    fn calldata_of_different_length_does_not_match() {
+        let mut buy = [1, 2, 3, 4, 5];
+        let mut sell = [1, 2, 3, 4, 5, 6];
+        assert!(!order_calldata_can_match(&mut buy, &[], &mut sell, &[]));
+    }
+
+    #[test]
+    fn calldata_without_selector_does_not_match() {
+        let mut buy = [1, 2, 3];
+        let mut sell = [1, 2, 3];
+        assert!(!order_calldata_can_match(&mut buy, &[], &mut sell, &[]));
+    }
+
+    #[test]
+    fn pattern_of_wrong_length_does_not_match() {
+        let mut buy = [1, 2, 3, 4, 5];
+        let mut sell = [1, 2, 3, 4, 6];
+        assert!(!order_calldata_can_match(&mut buy, &[], &mut sell, &[0, 0, 0, 0, 0xff, 0]));
+    }
+
+    #[test]
+    fn pattern_touching_selector_does_not_match() {
+        // The selectors differ, and a pattern allowing to replace them is rejected.
+        let mut buy = [9, 2, 3, 4, 5];
+        let mut sell = [1, 2, 3, 4, 5];
+        assert!(!order_calldata_can_match(&mut buy, &[0xff, 0, 0, 0, 0], &mut sell, &[]));
+        assert!(!order_calldata_can_match(&mut buy, &[], &mut sell, &[0x01, 0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn sell_pattern_lets_buyer_fill_in_argument() {
+        // The seller leaves the last byte, e.g. the recipient, for the buyer to fill in.
+        let mut buy = [1, 2, 3, 4, 7];
+        let mut sell = [1, 2, 3, 4, 0];
+        assert!(order_calldata_can_match(&mut buy, &[], &mut sell, &[0, 0, 0, 0, 0xff]));
+        assert_eq!(sell, [1, 2, 3, 4, 7]);
+        assert_eq!(buy, [1, 2, 3, 4, 7]);
+    }
+
+    #[test]
+    fn buy_pattern_is_applied_before_sell_pattern() {
+        // Both sides may replace the last byte: the buyer takes the seller's byte first, so the sell
+        // pattern then copies the already replaced buy calldata and both end with the seller's byte.
+        let mut buy = [1, 2, 3, 4, 7];
+        let mut sell = [1, 2, 3, 4, 8];
+        let pattern = [0, 0, 0, 0, 0xff];
+        assert!(order_calldata_can_match(&mut buy, &pattern, &mut sell, &pattern));
+        assert_eq!(buy, [1, 2, 3, 4, 8]);
+        assert_eq!(sell, [1, 2, 3, 4, 8]);
+    }
+
+    #[test]
+    fn unmasked_difference_does_not_match() {
+        let mut buy = [1, 2, 3, 4, 7, 1];
+        let mut sell = [1, 2, 3, 4, 0, 2];
+        assert!(!order_calldata_can_match(&mut buy, &[], &mut sell, &[0, 0, 0, 0, 0xff, 0]));
+    }
+}
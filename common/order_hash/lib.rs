@@ -0,0 +1,135 @@
+//   Canonical order hashing, shared by the exchange, the proxy registry and off-chain tooling so that all of them agree on a single digest.
+//   An order hash is Blake2-256 over the SCALE encoding of the order. The hash makers sign additionally commits to a domain separator,
+//   which binds a signature to one exchange contract on one chain.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink_env::{
+    hash::{
+        Blake2x256,
+        HashOutput,
+    },
+    AccountId,
+    Hash,
+};
+use ink_prelude::vec::Vec;
+use scale::Encode;
+
+/// Name of the exchange contract, part of the domain separator.
+pub const NAME: &[u8] = b"Wyvern Exchange Contract";
+
+/// Version of the exchange contract, part of the domain separator.
+pub const VERSION: &[u8] = b"2.3";
+
+/// Domain separator binding an order hash to one exchange contract on one chain.
+#[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub struct DomainSeparator {
+    /// Contract name, `NAME` for this exchange.
+    pub name: Vec<u8>,
+    /// Contract version, `VERSION` for this exchange.
+    pub version: Vec<u8>,
+    /// Genesis hash of the chain the exchange is deployed on.
+    pub genesis_hash: Hash,
+    /// The exchange contract.
+    pub exchange: AccountId,
+}
+
+impl DomainSeparator {
+    /// Domain separator of the exchange at `exchange` on the chain with `genesis_hash`.
+    pub fn new(genesis_hash: Hash, exchange: AccountId) -> Self {
+        Self {
+            name: NAME.to_vec(),
+            version: VERSION.to_vec(),
+            genesis_hash,
+            exchange,
+        }
+    }
+
+    /// Blake2-256 hash of the SCALE encoded domain separator.
+    pub fn hash(&self) -> Hash {
+        blake2_256(self)
+    }
+}
+
+/// Hash an order, returning the canonical order hash without the domain separator.
+///param order SCALE encodable order
+///param nonce Current nonce of the order maker; incrementing it invalidates every outstanding order of the maker
+///return Blake2-256 hash of the SCALE encoded order followed by the nonce
+pub fn hash_order<O: Encode>(order: &O, nonce: u64) -> Hash {
+    blake2_256(&(order, nonce))
+}
+
+/// Hash an order, returning the hash that a maker must sign.
+///param domain Domain separator of the exchange the order is intended for
+///param order SCALE encodable order
+///param nonce Current nonce of the order maker
+///return Blake2-256 hash of the domain separator hash followed by the order hash
+pub fn hash_to_sign<O: Encode>(domain: &DomainSeparator, order: &O, nonce: u64) -> Hash {
+    blake2_256(&(domain.hash(), hash_order(order, nonce)))
+}
+
+fn blake2_256<T: Encode>(value: &T) -> Hash {
+    let mut output = <Blake2x256 as HashOutput>::Type::default();
+    ink_env::hash_encoded::<Blake2x256, _>(value, &mut output);
+    Hash::from(output)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Expected digests, computed independently of this crate as Blake2-256 over the SCALE encodings of
+    // the domain separator below and of the order `(42u32, [3u8; 32])` with nonce 5.
+    const DOMAIN_HASH: [u8; 32] = [
+        0xd7, 0x53, 0xaf, 0x0c, 0xcd, 0x4e, 0xcd, 0xe6,
+        0x85, 0x4d, 0x60, 0x67, 0xdb, 0x2e, 0xb4, 0x37,
+        0xee, 0xe7, 0x87, 0x2d, 0xf1, 0x51, 0xbd, 0x5e,
+        0xbc, 0x51, 0xb4, 0xad, 0xda, 0x8b, 0xad, 0x87,
+    ];
+
+    const ORDER_HASH: [u8; 32] = [
+        0x44, 0xee, 0x26, 0x1c, 0xbf, 0x0e, 0x69, 0xa6,
+        0x22, 0x45, 0x8c, 0x49, 0xe2, 0xcf, 0xbe, 0x43,
+        0x15, 0x2c, 0x69, 0xc5, 0xb7, 0xbf, 0x7e, 0x3d,
+        0xb7, 0x48, 0x32, 0xd8, 0x09, 0x22, 0x1f, 0x5a,
+    ];
+
+    const HASH_TO_SIGN: [u8; 32] = [
+        0x59, 0x70, 0xb6, 0xd9, 0x4a, 0xa1, 0x15, 0xa8,
+        0x10, 0x6b, 0xa4, 0xc1, 0x25, 0xdd, 0xc6, 0x77,
+        0xbe, 0x58, 0x26, 0x7e, 0xb3, 0x2d, 0x54, 0xa0,
+        0x0e, 0x3a, 0x65, 0x3b, 0x35, 0x80, 0xe9, 0x25,
+    ];
+
+    fn domain() -> DomainSeparator {
+        DomainSeparator::new(Hash::from([1; 32]), AccountId::from([2; 32]))
+    }
+
+    fn order() -> (u32, [u8; 32]) {
+        (42, [3; 32])
+    }
+
+    #[test]
+    fn domain_separator_hash_matches_vector() {
+        assert_eq!(domain().hash(), Hash::from(DOMAIN_HASH));
+    }
+
+    #[test]
+    fn hash_order_matches_vector() {
+        assert_eq!(hash_order(&order(), 5), Hash::from(ORDER_HASH));
+    }
+
+    #[test]
+    fn hash_to_sign_matches_vector() {
+        assert_eq!(hash_to_sign(&domain(), &order(), 5), Hash::from(HASH_TO_SIGN));
+    }
+
+    #[test]
+    fn hashes_commit_to_nonce_and_domain() {
+        assert_ne!(hash_order(&order(), 6), Hash::from(ORDER_HASH));
+        let other_exchange = DomainSeparator::new(Hash::from([1; 32]), AccountId::from([4; 32]));
+        assert_ne!(hash_to_sign(&other_exchange, &order(), 5), Hash::from(HASH_TO_SIGN));
+    }
+}
@@ -2,7 +2,15 @@
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
+use ink_env::{
+    AccountId,
+    DefaultEnvironment,
+    Environment,
+};
 use ink_lang as ink;
+use ink_prelude::vec::Vec;
+
+type Balance = <DefaultEnvironment as Environment>::Balance;
 
 ///@title TokenRecipient
 ///@author Project Wyvern Developers
@@ -15,7 +23,8 @@ pub trait TokenRecipient {
         ///@param token of :AccountId token
         ///@param extra_data Additional data to log
         #[ink(message)]
-        pub fn receive_approval(
+        fn receive_approval(
+            &mut self,
             from: AccountId,
             value: Balance,
             token: AccountId,
@@ -34,6 +43,6 @@ pub trait TokenRecipient {
         /// The method needs to be annotated with `payable`; only then it is
         /// allowed to receive value as part of the call.
         #[ink(message, payable)]
-        pub fn was_it_ten(&self) ;
+        fn was_it_ten(&self) ;
 
 }
@@ -6,6 +6,7 @@
 pub use self::authenticated_proxy::{
     AuthenticatedProxy,
     AuthenticatedProxyRef,
+    HowToCall,
 };
 
 use ink_lang as ink;
@@ -15,20 +16,18 @@ mod authenticated_proxy {
     use ink_env::call::{
         build_call,
         Call,
+        DelegateCall,
         ExecutionInput,
+        Selector,
     };
     use ink_prelude::vec::Vec;
-    use ink_storage::{
-        traits::{
-            PackedLayout,
-            SpreadAllocate,
-            SpreadLayout,
-        },
-        Mapping,
+    use ink_storage::traits::{
+        PackedLayout,
+        SpreadAllocate,
+        SpreadLayout,
     };
     use scale::Output;
-         use token_recipient::TokenRecipient; 
-use owned_upgradeability_storage::OwnedUpgradeabilityStorage;
+    use token_recipient::TokenRecipient;
 
     /// A wrapper that allows us to encode a blob of bytes.
     ///
@@ -42,8 +41,16 @@ use owned_upgradeability_storage::OwnedUpgradeabilityStorage;
     }
 
 
+    /// Selector of `WyvernProxyRegistry::contracts`.
+    const REGISTRY_CONTRACTS_SELECTOR: [u8; 4] = [0x17, 0x1b, 0xa2, 0x92];
+
+    /// Selector of `PSP22::transfer_from`.
+    const PSP22_TRANSFER_FROM_SELECTOR: [u8; 4] = [0x54, 0xb3, 0xc7, 0x6e];
+
     /// Delegate call could be used to atomically transfer multiple assets owned by the proxy contract with one order.
-    #[derive(scale::Encode, scale::Decode, Clone, Copy, SpreadLayout, PackedLayout)]
+    #[derive(
+        Debug, PartialEq, Eq, scale::Encode, scale::Decode, Clone, Copy, SpreadLayout, PackedLayout,
+    )]
     #[cfg_attr(
         feature = "std",
         derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
@@ -52,13 +59,6 @@ use owned_upgradeability_storage::OwnedUpgradeabilityStorage;
         Call,
         DelegateCall,
     }
-    /// Errors that can occur upon calling this contract.
-    #[derive(Copy, Clone, Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
-    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
-    pub enum Error {
-        /// Returned if the call failed.
-        TransactionFailed,
-    }
 
 // TokenRecipient
     #[ink(event)]
@@ -98,7 +98,7 @@ use owned_upgradeability_storage::OwnedUpgradeabilityStorage;
         user: AccountId,
 
         /// Associated registry with contract authentication information.
-        registry: ProxyRegistry,
+        registry: AccountId,
 
         /// Whether access has been revoked.
         revoked: bool,
@@ -108,17 +108,17 @@ use owned_upgradeability_storage::OwnedUpgradeabilityStorage;
         /// Constructor that initializes the `bool` value to the given `init_value`.
         #[ink(constructor)]
         pub fn new() -> Self {
-            Self {}
+            ink_lang::utils::initialize_contract(|_: &mut Self| {})
         }
 
         ///Initialize an AuthenticatedProxy
-        ///@param addrUser of :AccountId user on whose behalf this proxy will act
+        ///@param addr_user of :AccountId user on whose behalf this proxy will act
         ///@param addr_registry of :AccountId ProxyRegistry contract which will manage this proxy
         #[ink(message)]
-        pub fn initialize(addrUser: AccountId, addr_registry: AccountId) {
+        pub fn initialize(&mut self, addr_user: AccountId, addr_registry: AccountId) {
             assert!(!self.initialized);
             self.initialized = true;
-            self.user = addrUser;
+            self.user = addr_user;
             self.registry = addr_registry;
         }
 
@@ -126,7 +126,7 @@ use owned_upgradeability_storage::OwnedUpgradeabilityStorage;
         ///@dev Can be called by the user only
         ///@param revoke Whether or not to revoke access
         #[ink(message)]
-        pub fn set_revoke(revoke: bool) {
+        pub fn set_revoke(&mut self, revoke: bool) {
             assert_eq!(self.env().caller(), self.user);
             self.revoked = revoke;
             self.env().emit_event(Revoked { revoked: revoke });
@@ -134,40 +134,50 @@ use owned_upgradeability_storage::OwnedUpgradeabilityStorage;
 
         ///Execute a message call from the proxy contract
         ///@dev Can be called by the user, or by a contract authorized by the registry as long as the user has not revoked access
-        ///@param dest to :AccountId which the call will be sent
+        ///@dev With `HowToCall::DelegateCall` the code of `dest`, e.g. the atomicizer, runs in the proxy's context, so the
+        ///     calls it makes originate from the proxy; ink! delegate calls target code, so `dest`'s code hash is used
+        ///@dev The call may use all gas left, bounded only by the gas limit of the caller's own call
+        ///@param dest to :AccountId which the call will be sent, or whose code is run for a delegate call
         ///@param how_to_call Which kind of call to make
         ///@param calldata Calldata to send
         ///@return Result of the call (success or failure)
         #[ink(message)]
-        pub fn proxy(dest: AccountId, how_to_call: HowToCall, calldata: Vec<u8>) -> bool {
-            assert!(
-                self.env().caller() == user
-                    || (!revoked && registry.contracts(self.env().caller()))
-            );
-            // if (how_to_call == HowToCall::Call) {
-            //      result = dest.call(calldata);
-            // } else if (how_to_call == HowToCall::DelegateCall) {
-            //      result = dest.delegatecall(calldata);
-            // }
-            let gas_limit=0;
-            let transferred_value = 0;
-            let mut selector=calldata;
-            let mut input=selector.split_off(4);
-            let result = build_call::<<Self as ::ink_lang::reflect::ContractEnv>::Env>()
-                .call_type(
-                    Call::new()
-                        .callee(dest)
-                        .gas_limit(gas_limit)
-                        .transferred_value(transferred_value),
-                )
-                .exec_input(
-                    ExecutionInput::new(selector.into()).push_arg(CallInput(&input)),
-                )
-                .returns::<()>()
-                .fire()
-                .map_err(|_| Error::TransactionFailed);
-
-             result.is_ok()
+        pub fn proxy(&mut self, dest: AccountId, how_to_call: HowToCall, calldata: Vec<u8>) -> bool {
+            assert!(self.caller_is_authorized());
+            if calldata.len() < 4 {
+                return false
+            }
+            let selector = Selector::new([calldata[0], calldata[1], calldata[2], calldata[3]]);
+            let input = ExecutionInput::new(selector).push_arg(CallInput(&calldata[4..]));
+            match how_to_call {
+                HowToCall::Call => {
+                    let gas_limit = 0;
+                    let transferred_value = 0;
+                    build_call::<<Self as ::ink_lang::reflect::ContractEnv>::Env>()
+                        .call_type(
+                            Call::new()
+                                .callee(dest)
+                                .gas_limit(gas_limit)
+                                .transferred_value(transferred_value),
+                        )
+                        .exec_input(input)
+                        .returns::<()>()
+                        .fire()
+                        .is_ok()
+                }
+                HowToCall::DelegateCall => {
+                    let code_hash = match self.env().code_hash(&dest) {
+                        Ok(code_hash) => code_hash,
+                        Err(_) => return false,
+                    };
+                    build_call::<<Self as ::ink_lang::reflect::ContractEnv>::Env>()
+                        .call_type(DelegateCall::new().code_hash(code_hash))
+                        .exec_input(input)
+                        .returns::<()>()
+                        .fire()
+                        .is_ok()
+                }
+            }
         }
 
         ///Execute a message call and assert success
@@ -178,8 +188,24 @@ use owned_upgradeability_storage::OwnedUpgradeabilityStorage;
         ///@param calldata Calldata to send
 
         #[ink(message)]
-        pub fn proxy_assert(dest: AccountId, how_to_call: HowToCall, calldata: Vec<u8>) {
-            assert!(proxy(dest, how_to_call, calldata));
+        pub fn proxy_assert(&mut self, dest: AccountId, how_to_call: HowToCall, calldata: Vec<u8>) {
+            assert!(self.proxy(dest, how_to_call, calldata));
+        }
+
+        /// Whether the caller is the user, or a contract authorized by the registry while access is not revoked.
+        fn caller_is_authorized(&self) -> bool {
+            let caller = self.env().caller();
+            caller == self.user || (!self.revoked && self.registry_contracts(caller))
+        }
+
+        /// Whether the registry authorizes `addr` to call the proxies.
+        fn registry_contracts(&self, addr: AccountId) -> bool {
+            build_call::<<Self as ::ink_lang::reflect::ContractEnv>::Env>()
+                .call_type(Call::new().callee(self.registry).gas_limit(0).transferred_value(0))
+                .exec_input(ExecutionInput::new(REGISTRY_CONTRACTS_SELECTOR.into()).push_arg(addr))
+                .returns::<bool>()
+                .fire()
+                .unwrap_or(false)
         }
     }
 
@@ -191,7 +217,8 @@ impl TokenRecipient for AuthenticatedProxy{
         ///@param token of :AccountId token
         ///@param extra_data Additional data to log
         #[ink(message)]
-        pub fn receive_approval(
+        fn receive_approval(
+            &mut self,
             from: AccountId,
             value: Balance,
             token: AccountId,
@@ -202,7 +229,6 @@ impl TokenRecipient for AuthenticatedProxy{
             // emit ReceivedTokens(from, value, token, extra_data);
             let gas_limit = 0;
             let transferred_value = 0;
-            let selector = [0x0b, 0x39, 0x6f, 0x18];
             let result = build_call::<<Self as ::ink_lang::reflect::ContractEnv>::Env>()
                 .call_type(
                     Call::new()
@@ -211,15 +237,15 @@ impl TokenRecipient for AuthenticatedProxy{
                         .transferred_value(transferred_value),
                 )
                 .exec_input(
-                    ExecutionInput::new(selector.into())
+                    ExecutionInput::new(PSP22_TRANSFER_FROM_SELECTOR.into())
                         .push_arg(from)
                         .push_arg(self.env().account_id())
-                        .push_arg(values),
+                        .push_arg(value)
+                        .push_arg(Vec::<u8>::new()),
                 )
-                .returns::<()>()
-                .fire()
-                .map_err(|_| Error::TransactionFailed);
-            assert!(result.is_ok());
+                .returns::<Result<(), ()>>()
+                .fire();
+            assert!(matches!(result, Ok(Ok(()))));
             self.env().emit_event(ReceivedTokens {
                 from,
                 value,
@@ -240,7 +266,7 @@ impl TokenRecipient for AuthenticatedProxy{
         /// The method needs to be annotated with `payable`; only then it is
         /// allowed to receive value as part of the call.
         #[ink(message, payable)]
-        pub fn was_it_ten(&self) {
+        fn was_it_ten(&self) {
             ink_env::debug_println!("received payment: {}", self.env().transferred_value());
             assert!(self.env().transferred_value() == 10, "payment was not ten");
             self.env().emit_event(ReceivedEther {
@@ -251,25 +277,6 @@ impl TokenRecipient for AuthenticatedProxy{
     }
 
 
-impl OwnedUpgradeabilityStorage for OwnableDelegateProxy {
-     
-///dev Tells the of :AccountId the owner
-        ///return the of :AccountId the owner
-        #[ink(message)]
-        pub fn upgradeability_owner() -> AccountId {
-            *self._upgradeability_owner
-        }
-
-        ///dev Sets the of :AccountId the owner
-        #[ink(message)]
-        pub fn set_upgradeability_owner(new_upgradeability_owner: AccountId) {
-            *self._upgradeability_owner = new_upgradeability_owner;
-        }
-
-
-    }
-
-
     /// Unit tests in Rust are normally defined within such a `#[cfg(test)]`
     /// module and test functions are marked with a `#[test]` attribute.
     /// The below code is technically just normal Rust code.
@@ -281,20 +288,36 @@ impl OwnedUpgradeabilityStorage for OwnableDelegateProxy {
         /// Imports `ink_lang` so we can use `#[ink::test]`.
         use ink_lang as ink;
 
-        // /// We test if the default constructor does its job.
-        // #[ink::test]
-        // fn default_works() {
-        //     let authenticated_proxy = AuthenticatedProxy::default();
-        //     assert_eq!(authenticated_proxy.get(), false);
-        // }
+        fn default_accounts() -> ink_env::test::DefaultAccounts<Environment> {
+            ink_env::test::default_accounts::<Environment>()
+        }
 
-        // /// We test a simple use case of our contract.
-        // #[ink::test]
-        // fn it_works() {
-        //     let mut authenticated_proxy = AuthenticatedProxy::new(false);
-        //     assert_eq!(authenticated_proxy.get(), false);
-        //     authenticated_proxy.flip();
-        //     assert_eq!(authenticated_proxy.get(), true);
-        // }
+        /// A proxy of alice, called by alice so that no registry lookup is needed.
+        fn build_contract() -> AuthenticatedProxy {
+            let accounts = default_accounts();
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            let mut proxy = AuthenticatedProxy::new();
+            proxy.initialize(accounts.alice, accounts.django);
+            proxy
+        }
+
+        #[ink::test]
+        fn calldata_needs_a_selector() {
+            let accounts = default_accounts();
+            let mut proxy = build_contract();
+
+            assert!(!proxy.proxy(accounts.frank, HowToCall::Call, vec![0x84, 0xa1, 0x5d]));
+            assert!(!proxy.proxy(accounts.frank, HowToCall::Call, Vec::new()));
+            assert!(!proxy.proxy(accounts.frank, HowToCall::DelegateCall, vec![0x84, 0xa1, 0x5d]));
+        }
+
+        #[ink::test]
+        #[should_panic]
+        fn proxy_cannot_be_initialized_twice() {
+            let accounts = default_accounts();
+            let mut proxy = build_contract();
+
+            proxy.initialize(accounts.bob, accounts.django);
+        }
     }
 }
@@ -55,12 +55,18 @@
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
-pub use self::wyvern_atomicizer::WyvernAtomicizer;
+pub use self::wyvern_atomicizer::{
+    CallError,
+    WyvernAtomicizer,
+};
 use ink_lang as ink;
 
 #[ink::contract]
 mod wyvern_atomicizer {
-    use ink_env::call::{build_call, Call, ExecutionInput};
+    use ink_env::{
+        call::{build_call, Call, ExecutionInput, Selector},
+        ReturnFlags,
+    };
     use ink_prelude::vec::Vec;
     use ink_storage::{
         traits::{PackedLayout, SpreadAllocate, SpreadLayout},
@@ -91,6 +97,58 @@ mod wyvern_atomicizer {
     pub enum Error {
         /// Returned if the call failed.
         TransactionFailed,
+        /// Returned if `callees` and `values` differ in length.
+        LengthMismatch,
+        /// Returned if a calldata is shorter than a message selector.
+        InvalidCalldata,
+        /// Returned if the values of a batch add up to more than the transferred value.
+        InsufficientValue,
+        /// Returned if a call of a batch has no gas limit.
+        InvalidGasLimit,
+        /// Returned if the gas limits of a batch add up to more than its gas budget.
+        GasBudgetExceeded,
+        /// Returned if less gas is left than the gas budget of a batch.
+        InsufficientGas,
+    }
+
+    /// Why a call of an evaluated batch failed, mirroring the environment error of the call.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub enum CallError {
+        /// The callee trapped.
+        CalleeTrapped,
+        /// The callee reverted, e.g. because the message returned an `Err`.
+        CalleeReverted,
+        /// The value could not be transferred to the callee.
+        TransferFailed,
+        /// The callee is not a contract.
+        NotCallable,
+        /// Any other environment error.
+        Other,
+    }
+
+    impl From<ink_env::Error> for CallError {
+        fn from(error: ink_env::Error) -> Self {
+            match error {
+                ink_env::Error::CalleeTrapped => CallError::CalleeTrapped,
+                ink_env::Error::CalleeReverted => CallError::CalleeReverted,
+                ink_env::Error::TransferFailed => CallError::TransferFailed,
+                ink_env::Error::NotCallable => CallError::NotCallable,
+                _ => CallError::Other,
+            }
+        }
+    }
+
+    /// Raw output of a call, decoded without assuming its type.
+    struct CallOutput(Vec<u8>);
+
+    impl scale::Decode for CallOutput {
+        fn decode<I: scale::Input>(input: &mut I) -> Result<Self, scale::Error> {
+            let len = input.remaining_len()?.unwrap_or_default();
+            let mut output = ink_prelude::vec![0; len];
+            input.read(&mut output)?;
+            Ok(Self(output))
+        }
     }
 
     /// Emitted when an owner confirms a transaction.
@@ -140,12 +198,15 @@ mod wyvern_atomicizer {
             ink_lang::utils::initialize_contract(|contract: &mut Self| {})
         }
 
-        /// Evaluate a confirmed execution and return its output as Vec<u8>.
+        /// Call `selector` with `(from, to, values[i])` on every callee, all or nothing.
+        ///
+        /// If any call fails an error is returned and the whole message reverts, so that either
+        /// every transfer of a bundle happens or none does.
         ///
-        /// Its return value indicates whether the called transaction was successful and contains
-        /// its output when successful.
+        /// The calls may use all gas left, bounded only by the gas limit of this call; use
+        /// `atomicize_calls` to bound each call.
         /// This can be called by anyone.
-        #[ink(message, payable)]
+        #[ink(message)]
         pub fn atomicize(
             &mut self,
             selector: [u8; 4],
@@ -154,6 +215,9 @@ mod wyvern_atomicizer {
             to: AccountId,
             values: Vec<Balance>,
         ) -> Result<(), Error> {
+            if callees.len() != values.len() {
+                return Err(Error::LengthMismatch)
+            }
             let transferred_value = Balance::default();
             let gas_limit = 0;
 
@@ -179,17 +243,78 @@ mod wyvern_atomicizer {
                     value: values[i],
                     result: result.map(|_| None),
                 });
+                result?;
             }
             self.env().emit_event(Confirmation { selector, from, to });
             Ok(())
         }
 
-        /// Evaluate a confirmed execution and return its output as Vec<u8>.
+        /// Execute a batch of arbitrary calls, all or nothing.
+        ///
+        /// Each call is a `(callee, value, gas_limit, calldata)` tuple, where `calldata` is the SCALE
+        /// encoded call: the 4 byte message selector followed by the encoded arguments. This allows
+        /// one batch to mix PSP22 transfers, PSP34 transfers and any other contract call.
+        /// If any call fails an error is returned and the whole message reverts.
+        ///
+        /// The values of the calls are paid out of the value transferred to this message: the
+        /// message reverts if they add up to more, and the excess is refunded to the caller.
         ///
-        /// Its return value indicates whether the called transaction was successful and contains
-        /// its output when successful.
+        /// Every call must set a non-zero gas limit, and the limits must add up to at most
+        /// `gas_budget`, so that one callee cannot consume the gas of the whole batch and the
+        /// worst-case weight of a batch is known upfront. The batch is rejected upfront if less
+        /// gas is left than `gas_budget`, rather than failing on whichever call runs out.
         /// This can be called by anyone.
         #[ink(message, payable)]
+        pub fn atomicize_calls(
+            &mut self,
+            calls: Vec<(AccountId, Balance, u64, Vec<u8>)>,
+            gas_budget: u64,
+        ) -> Result<(), Error> {
+            let transferred_value = self.env().transferred_value();
+            let total_value = calls
+                .iter()
+                .try_fold(Balance::default(), |total, (_, value, _, _)| total.checked_add(*value))
+                .ok_or(Error::InsufficientValue)?;
+            if total_value > transferred_value {
+                return Err(Error::InsufficientValue)
+            }
+
+            self.execute_calls(&calls, gas_budget)?;
+
+            let refund = transferred_value - total_value;
+            if refund > 0 {
+                self.env()
+                    .transfer(self.env().caller(), refund)
+                    .map_err(|_| Error::TransactionFailed)?;
+            }
+            Ok(())
+        }
+
+        /// Execute a batch of arbitrary calls, all or nothing, in the context of the caller.
+        ///
+        /// This is the entry point for an `AuthenticatedProxy` reaching the atomicizer with
+        /// `HowToCall::DelegateCall` and the atomicizer's code hash: the code runs in the proxy's
+        /// context, so the calls of the batch originate from the proxy's account and their values
+        /// are paid from the proxy's balance. The atomicizer has no storage, so running its code
+        /// on the proxy's storage leaves the latter untouched.
+        ///
+        /// Calls, gas limits and `gas_budget` are as for `atomicize_calls`.
+        #[ink(message)]
+        pub fn delegate_atomicize(
+            &self,
+            calls: Vec<(AccountId, Balance, u64, Vec<u8>)>,
+            gas_budget: u64,
+        ) -> Result<(), Error> {
+            self.execute_calls(&calls, gas_budget)
+        }
+
+        /// Evaluate the calls of `atomicize` and return the raw output of each.
+        ///
+        /// Every call is attempted even if earlier ones fail, so that a dry-run can inspect the
+        /// output or error of every call of a batch in one query. The message always reverts, so
+        /// none of the calls takes effect even when it is submitted as a transaction.
+        /// This can be called by anyone.
+        #[ink(message)]
         pub fn eval_atomicize(
             &mut self,
             selector: [u8; 4],
@@ -197,9 +322,13 @@ mod wyvern_atomicizer {
             from: AccountId,
             to: AccountId,
             values: Vec<Balance>,
-        ) -> Result<(), Error> {
+        ) -> Result<Vec<Result<Vec<u8>, CallError>>, Error> {
+            if callees.len() != values.len() {
+                return Err(Error::LengthMismatch)
+            }
             let transferred_value = Balance::default();
             let gas_limit = 0;
+            let mut results = Vec::with_capacity(callees.len());
             for (i, &callee) in callees.iter().enumerate() {
                 let result = build_call::<<Self as ::ink_lang::reflect::ContractEnv>::Env>()
                     .call_type(
@@ -214,16 +343,73 @@ mod wyvern_atomicizer {
                             .push_arg(to)
                             .push_arg(values[i]),
                     )
-                    .returns::<Vec<u8>>()
+                    .returns::<CallOutput>()
                     .fire()
-                    .map_err(|_| Error::TransactionFailed);
+                    .map(|output| output.0)
+                    .map_err(CallError::from);
                 self.env().emit_event(Execution {
                     callee,
                     value: values[i],
-                    result: result.clone().map(Some),
+                    result: result
+                        .clone()
+                        .map(Some)
+                        .map_err(|_| Error::TransactionFailed),
                 });
+                results.push(result);
             }
             self.env().emit_event(Confirmation { selector, from, to });
+            ink_env::return_value::<Result<Vec<Result<Vec<u8>, CallError>>, Error>>(
+                ReturnFlags::default().set_reverted(true),
+                &Ok(results),
+            )
+        }
+
+        /// Execute `calls` from the executing contract's account, failing on the first failed call.
+        fn execute_calls(
+            &self,
+            calls: &[(AccountId, Balance, u64, Vec<u8>)],
+            gas_budget: u64,
+        ) -> Result<(), Error> {
+            if calls.iter().any(|(_, _, _, calldata)| calldata.len() < 4) {
+                return Err(Error::InvalidCalldata)
+            }
+            if calls.iter().any(|(_, _, gas_limit, _)| *gas_limit == 0) {
+                return Err(Error::InvalidGasLimit)
+            }
+            let total_gas = calls
+                .iter()
+                .try_fold(0u64, |total, (_, _, gas_limit, _)| total.checked_add(*gas_limit))
+                .ok_or(Error::GasBudgetExceeded)?;
+            if total_gas > gas_budget {
+                return Err(Error::GasBudgetExceeded)
+            }
+            if gas_budget > 0 && gas_budget > self.env().gas_left() {
+                return Err(Error::InsufficientGas)
+            }
+
+            for (callee, value, gas_limit, calldata) in calls.iter() {
+                let selector = [calldata[0], calldata[1], calldata[2], calldata[3]];
+                let result = build_call::<<Self as ::ink_lang::reflect::ContractEnv>::Env>()
+                    .call_type(
+                        Call::new()
+                            .callee(*callee)
+                            .gas_limit(*gas_limit)
+                            .transferred_value(*value),
+                    )
+                    .exec_input(
+                        ExecutionInput::new(Selector::new(selector))
+                            .push_arg(CallInput(&calldata[4..])),
+                    )
+                    .returns::<()>()
+                    .fire()
+                    .map_err(|_| Error::TransactionFailed);
+                self.env().emit_event(Execution {
+                    callee: *callee,
+                    value: *value,
+                    result: result.map(|_| None),
+                });
+                result?;
+            }
             Ok(())
         }
     }
@@ -259,6 +445,30 @@ mod wyvern_atomicizer {
             ink_env::test::default_accounts::<Environment>()
         }
 
+        fn balance_of(account: AccountId) -> Balance {
+            ink_env::test::get_account_balance::<Environment>(account).unwrap_or_default()
+        }
+
+        /// Transfer `value` to the atomicizer along with the next call, as `caller`.
+        fn set_value_transferred(caller: AccountId, value: Balance) {
+            let wallet = AccountId::from(WALLET);
+            set_caller(caller);
+            ink_env::test::set_account_balance::<Environment>(wallet, balance_of(wallet) + value);
+            ink_env::test::set_value_transferred::<Environment>(value);
+        }
+
+        /// A PSP22 `transfer` of `amount` to `to` on `token`.
+        fn transfer_call(
+            token: AccountId,
+            to: AccountId,
+            amount: Balance,
+            gas_limit: u64,
+        ) -> (AccountId, Balance, u64, Vec<u8>) {
+            let mut calldata = vec![0xdb, 0x20, 0xf9, 0xf5];
+            scale::Encode::encode_to(&(to, amount, Vec::<u8>::new()), &mut calldata);
+            (token, 0, gas_limit, calldata)
+        }
+
         fn build_contract() -> WyvernAtomicizer {
             // Set the contract's address as `WALLET`.
             let callee: AccountId = AccountId::from(WALLET);
@@ -268,5 +478,102 @@ mod wyvern_atomicizer {
             let owners = vec![accounts.alice, accounts.bob, accounts.eve];
             WyvernAtomicizer::new()
         }
+
+        #[ink::test]
+        fn excess_value_is_refunded() {
+            let accounts = default_accounts();
+            let mut atomicizer = build_contract();
+            let before = balance_of(accounts.bob);
+
+            set_value_transferred(accounts.bob, 100);
+            assert_eq!(atomicizer.atomicize_calls(Vec::new(), 0), Ok(()));
+
+            assert_eq!(balance_of(accounts.bob), before + 100);
+            assert_eq!(balance_of(AccountId::from(WALLET)), 0);
+        }
+
+        #[ink::test]
+        fn underfunded_batch_is_rejected() {
+            let accounts = default_accounts();
+            let mut atomicizer = build_contract();
+            let mut call = transfer_call(accounts.django, accounts.eve, 10, 1_000);
+            call.1 = 50;
+
+            set_value_transferred(accounts.bob, 49);
+            assert_eq!(
+                atomicizer.atomicize_calls(vec![call], 1_000),
+                Err(Error::InsufficientValue)
+            );
+        }
+
+        #[ink::test]
+        fn calls_need_a_gas_limit() {
+            let accounts = default_accounts();
+            let atomicizer = build_contract();
+            let calls = vec![
+                transfer_call(accounts.django, accounts.eve, 10, 1_000),
+                transfer_call(accounts.django, accounts.frank, 10, 0),
+            ];
+
+            assert_eq!(
+                atomicizer.delegate_atomicize(calls, 2_000),
+                Err(Error::InvalidGasLimit)
+            );
+        }
+
+        #[ink::test]
+        fn gas_budget_is_enforced() {
+            let accounts = default_accounts();
+            let atomicizer = build_contract();
+            let calls = vec![
+                transfer_call(accounts.django, accounts.eve, 10, 1_000),
+                transfer_call(accounts.django, accounts.frank, 10, 1_001),
+            ];
+
+            assert_eq!(
+                atomicizer.delegate_atomicize(calls, 2_000),
+                Err(Error::GasBudgetExceeded)
+            );
+        }
+
+        #[ink::test]
+        fn gas_limit_overflow_exceeds_budget() {
+            let accounts = default_accounts();
+            let atomicizer = build_contract();
+            let calls = vec![
+                transfer_call(accounts.django, accounts.eve, 10, u64::MAX),
+                transfer_call(accounts.django, accounts.frank, 10, 1),
+            ];
+
+            assert_eq!(
+                atomicizer.delegate_atomicize(calls, u64::MAX),
+                Err(Error::GasBudgetExceeded)
+            );
+        }
+
+        #[ink::test]
+        fn calldata_needs_a_selector() {
+            let accounts = default_accounts();
+            let atomicizer = build_contract();
+            let calls = vec![
+                transfer_call(accounts.django, accounts.eve, 10, 1_000),
+                (accounts.django, 0, 1_000, vec![0xdb, 0x20, 0xf9]),
+            ];
+
+            assert_eq!(
+                atomicizer.delegate_atomicize(calls, 2_000),
+                Err(Error::InvalidCalldata)
+            );
+        }
+
+        #[test]
+        fn call_errors_mirror_environment_errors() {
+            assert_eq!(CallError::from(ink_env::Error::CalleeTrapped), CallError::CalleeTrapped);
+            assert_eq!(CallError::from(ink_env::Error::CalleeReverted), CallError::CalleeReverted);
+            assert_eq!(CallError::from(ink_env::Error::TransferFailed), CallError::TransferFailed);
+            assert_eq!(CallError::from(ink_env::Error::NotCallable), CallError::NotCallable);
+            assert_eq!(CallError::from(ink_env::Error::CodeNotFound), CallError::Other);
+            assert_eq!(CallError::from(ink_env::Error::KeyNotFound), CallError::Other);
+        }
     }
 }
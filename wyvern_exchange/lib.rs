@@ -0,0 +1,1420 @@
+//   Exchange core; matches a buy order against a sell order and executes the agreed call through the seller's AuthenticatedProxy.
+//   Proxies are looked up in the WyvernProxyRegistry, which must have authenticated this exchange for the call to be accepted.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub use self::{
+    signature::Signature,
+    wyvern_exchange::{
+        Error,
+        FeeMethod,
+        Order,
+        SaleKind,
+        Side,
+        WyvernExchange,
+        WyvernExchangeRef,
+    },
+};
+
+use ink_lang as ink;
+
+mod signature;
+
+#[ink::contract]
+mod wyvern_exchange {
+    use authenticated_proxy::{
+        AuthenticatedProxyRef,
+        HowToCall,
+    };
+    use crate::signature::Signature;
+    use ink_env::{
+        call::{
+            build_call,
+            Call,
+            ExecutionInput,
+            FromAccountId,
+        },
+    };
+    use ink_lang::codegen::EmitEvent;
+    use ink_prelude::vec::Vec;
+    use ink_storage::{
+        traits::SpreadAllocate,
+        Mapping,
+    };
+    use order_hash::DomainSeparator;
+    use scale::Output;
+    use wyvern_token_transfer_proxy::WyvernTokenTransferProxyRef;
+
+    /// Inverse basis point: fees are expressed in hundredths of a percent of the match price.
+    const INVERSE_BASIS_POINT: Balance = 10_000;
+
+    /// Gas limit of a static call, so that a predicate cannot consume the gas of the whole match.
+    const STATIC_CALL_GAS_LIMIT: u64 = 5_000_000_000;
+
+    /// Selector of `WyvernProxyRegistry::proxies`.
+    const REGISTRY_PROXIES_SELECTOR: [u8; 4] = [0x14, 0x4b, 0x05, 0x35];
+
+    /// A wrapper that allows us to encode a blob of bytes.
+    ///
+    /// We use this to pass the set of untyped (bytes) parameters to the `CallBuilder`.
+    struct CallInput<'a>(&'a [u8]);
+
+    impl<'a> scale::Encode for CallInput<'a> {
+        fn encode_to<T: Output + ?Sized>(&self, dest: &mut T) {
+            dest.write(self.0);
+        }
+    }
+
+    /// Errors that can occur upon calling this contract.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub enum Error {
+        /// Returned if the order is intended for another exchange contract.
+        WrongExchange,
+        /// Returned if the sale kind parameters are inconsistent, e.g. a Dutch auction without expiration.
+        InvalidSaleKind,
+        /// Returned if the static call extra data does not start with a message selector.
+        InvalidStaticExtradata,
+        /// Returned if the order was cancelled or already matched.
+        AlreadyFinalized,
+        /// Returned if the order is neither submitted, approved nor validly signed by its maker.
+        InvalidSignature,
+        /// Returned if the caller is not the maker of the order.
+        NotMaker,
+        /// Returned if the order was already approved.
+        AlreadyApproved,
+        /// Returned if the orders are not a buy order and a sell order.
+        SideMismatch,
+        /// Returned if the orders are paid with different tokens.
+        PaymentTokenMismatch,
+        /// Returned if an order is restricted to another taker.
+        TakerMismatch,
+        /// Returned if the orders target different contracts or call them differently.
+        TargetMismatch,
+        /// Returned if an order is not listed yet.
+        NotYetListed,
+        /// Returned if an order has expired.
+        Expired,
+        /// Returned if the buy price is below the sell price.
+        PriceMismatch,
+        /// Returned if a price computation overflowed.
+        PriceOverflow,
+        /// Returned if the calldata of both orders cannot be merged into the same call.
+        CalldataMismatch,
+        /// Returned if the call target is not a contract.
+        TargetNotContract,
+        /// Returned if the seller has no proxy registered in the proxy registry.
+        ProxyMissing,
+        /// Returned if the call through the seller's proxy failed.
+        ProxyCallFailed,
+        /// Returned if a static call predicate failed or returned `false`.
+        StaticCallFailed,
+        /// Returned if the maker's taker fees exceed the maximum fees accepted by the taker.
+        FeeMismatch,
+        /// Returned if fees are charged by a buy-side fee recipient on an order paid in the native currency.
+        UnsupportedPayment,
+        /// Returned if value is transferred to match orders paid in a token.
+        ValueMismatch,
+        /// Returned if the transferred value is below the amount due in the native currency.
+        InsufficientValue,
+        /// Returned if a payment or fee transfer failed.
+        TransferFailed,
+        /// Returned if the caller is not the owner of the exchange.
+        NotOwner,
+        /// Returned if the orders use different fee methods.
+        FeeMethodMismatch,
+        /// Returned if not exactly one of the orders names a fee recipient.
+        FeeRecipientMismatch,
+    }
+
+    /// Side of an order: whether the maker buys or sells the asset moved by the call.
+    #[derive(Debug, PartialEq, Eq, Clone, Copy, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Side {
+        Buy,
+        Sell,
+    }
+
+    /// Fee method: who pays the fees of the order setting the fee recipient.
+    #[derive(Debug, PartialEq, Eq, Clone, Copy, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum FeeMethod {
+        /// Only the maker fees are charged, to the maker; the taker fees are ignored.
+        ProtocolFee,
+        /// The maker fees are charged to the maker and the taker fees to the counterparty.
+        SplitFee,
+    }
+
+    /// Kind of sale: fixed price or a Dutch auction whose price moves by `extra` over the order's lifetime.
+    #[derive(Debug, PartialEq, Eq, Clone, Copy, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum SaleKind {
+        FixedPrice,
+        DutchAuction,
+    }
+
+    /// An order on the exchange.
+    #[derive(Debug, PartialEq, Eq, Clone, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct Order {
+        /// Exchange the order is intended for, used as a versioning mechanism.
+        pub exchange: AccountId,
+        /// Order maker.
+        pub maker: AccountId,
+        /// Order taker, `None` if anyone may take the order.
+        pub taker: Option<AccountId>,
+        /// Maker relayer fee of the order, in basis points of the match price.
+        pub maker_relayer_fee: Balance,
+        /// Taker relayer fee of the order, in basis points of the match price.
+        pub taker_relayer_fee: Balance,
+        /// Maker protocol fee of the order, in basis points of the match price.
+        pub maker_protocol_fee: Balance,
+        /// Taker protocol fee of the order, in basis points of the match price.
+        pub taker_protocol_fee: Balance,
+        /// Order fee recipient (relayer), `None` if the fees are set by the counterparty's order.
+        pub fee_recipient: Option<AccountId>,
+        /// Fee method (protocol fee or split fee).
+        pub fee_method: FeeMethod,
+        /// Side (buy/sell).
+        pub side: Side,
+        /// Kind of sale.
+        pub sale_kind: SaleKind,
+        /// Target of the call executed through the seller's proxy.
+        /// For `HowToCall::DelegateCall`, the contract whose code the proxy runs in its own context, e.g. the atomicizer.
+        pub target: AccountId,
+        /// Which kind of call the proxy makes to `target`.
+        pub how_to_call: HowToCall,
+        /// SCALE encoded call: the 4 byte message selector followed by the encoded arguments.
+        pub calldata: Vec<u8>,
+        /// Calldata replacement pattern, or an empty vector for no replacement.
+        /// Set bits may be filled in from the counterparty's calldata; the selector bytes must stay zero.
+        pub replacement_pattern: Vec<u8>,
+        /// Static call target, `None` for no static call.
+        /// The target is a predicate checked after the match, e.g. that the buyer now owns the traded token.
+        pub static_target: Option<AccountId>,
+        /// Static call extra data: the predicate's 4 byte selector followed by its leading encoded arguments.
+        /// The merged calldata of the match is appended as a final `Vec<u8>` argument.
+        pub static_extradata: Vec<u8>,
+        /// Token used to pay for the order, `None` for the chain's native currency.
+        pub payment_token: Option<AccountId>,
+        /// Base price of the order (in units of the payment token).
+        pub base_price: Balance,
+        /// Auction extra parameter - maximum price change over the order's lifetime for a Dutch auction.
+        pub extra: Balance,
+        /// Listing timestamp.
+        pub listing_time: Timestamp,
+        /// Expiration timestamp - 0 for no expiry.
+        pub expiration_time: Timestamp,
+        /// Order salt, used to prevent duplicate hashes.
+        pub salt: u64,
+    }
+
+    /// Emitted when a maker approves an order on-chain.
+    ///dev Carries the full order so that indexers can rebuild the order book from events alone
+    #[ink(event)]
+    pub struct OrderApproved {
+        #[ink(topic)]
+        hash: Hash,
+        #[ink(topic)]
+        maker: AccountId,
+        order: Order,
+        order_book_inclusion_desired: bool,
+    }
+
+    /// Emitted when a maker cancels an order.
+    #[ink(event)]
+    pub struct OrderCancelled {
+        #[ink(topic)]
+        hash: Hash,
+    }
+
+    /// Emitted when two orders are matched.
+    #[ink(event)]
+    pub struct OrdersMatched {
+        buy_hash: Hash,
+        sell_hash: Hash,
+        #[ink(topic)]
+        maker: AccountId,
+        #[ink(topic)]
+        taker: AccountId,
+        price: Balance,
+        #[ink(topic)]
+        metadata: Hash,
+    }
+
+    /// Emitted when a maker increments their nonce, invalidating all of their outstanding orders.
+    #[ink(event)]
+    pub struct NonceIncremented {
+        #[ink(topic)]
+        maker: AccountId,
+        new_nonce: u64,
+    }
+
+    /// Defines the storage of your contract.
+    /// Add new fields to the below struct in order
+    /// to add new static storage fields to your contract.
+    #[ink(storage)]
+    #[derive(SpreadAllocate)]
+    pub struct WyvernExchange {
+        /// Owner of the exchange, allowed to change the protocol fee recipient.
+        owner: AccountId,
+        /// Proxy registry contract holding the users' AuthenticatedProxy contracts.
+        registry: AccountId,
+        /// Token transfer proxy contract, moving the payment tokens approved by the users.
+        token_transfer_proxy: AccountId,
+        /// Recipient of protocol fees.
+        protocol_fee_recipient: AccountId,
+        /// Genesis hash of the chain, part of the domain separator.
+        genesis_hash: Hash,
+        /// Orders verified by on-chain approval (alternative to signatures, so that smart contracts can place orders directly).
+        approved_orders: Mapping<Hash, bool>,
+        /// Cancelled / finalized orders, by hash.
+        cancelled_or_finalized: Mapping<Hash, bool>,
+        /// Current nonce of each maker, committed to by the hash of their orders.
+        nonces: Mapping<AccountId, u64>,
+    }
+
+    impl WyvernExchange {
+        /// Instantiate an exchange bound to the given proxy registry.
+        ///dev The registry must grant authentication to this exchange before any order can be matched
+        ///param registry :AccountId of the WyvernProxyRegistry
+        ///param token_transfer_proxy :AccountId of the WyvernTokenTransferProxy
+        ///param protocol_fee_recipient :AccountId receiving protocol fees
+        ///param genesis_hash Genesis hash of the chain the exchange is deployed on
+        #[ink(constructor)]
+        pub fn new(
+            registry: AccountId,
+            token_transfer_proxy: AccountId,
+            protocol_fee_recipient: AccountId,
+            genesis_hash: Hash,
+        ) -> Self {
+            ink_lang::utils::initialize_contract(|contract: &mut Self| {
+                contract.owner = Self::env().caller();
+                contract.registry = registry;
+                contract.token_transfer_proxy = token_transfer_proxy;
+                contract.protocol_fee_recipient = protocol_fee_recipient;
+                contract.genesis_hash = genesis_hash;
+            })
+        }
+
+        /// Returns the recipient of protocol fees.
+        #[ink(message)]
+        pub fn protocol_fee_recipient(&self) -> AccountId {
+            self.protocol_fee_recipient
+        }
+
+        /// Change the protocol fee recipient (owner only)
+        ///param new_protocol_fee_recipient New protocol fee recipient :AccountId
+        #[ink(message)]
+        pub fn change_protocol_fee_recipient(
+            &mut self,
+            new_protocol_fee_recipient: AccountId,
+        ) -> Result<(), Error> {
+            if self.env().caller() != self.owner {
+                return Err(Error::NotOwner)
+            }
+            self.protocol_fee_recipient = new_protocol_fee_recipient;
+            Ok(())
+        }
+
+        /// Domain separator committed to by every hash a maker signs.
+        #[ink(message)]
+        pub fn domain_separator(&self) -> DomainSeparator {
+            DomainSeparator::new(self.genesis_hash, self.env().account_id())
+        }
+
+        /// Hash an order, returning the canonical order hash without the domain separator.
+        #[ink(message)]
+        pub fn hash_order(&self, order: Order) -> Hash {
+            order_hash::hash_order(&order, self.nonces(order.maker))
+        }
+
+        /// Hash an order, returning the hash that a maker must sign.
+        #[ink(message)]
+        pub fn hash_to_sign(&self, order: Order) -> Hash {
+            self.required_hash(&order)
+        }
+
+        /// Returns the current nonce of `maker`.
+        #[ink(message)]
+        pub fn nonces(&self, maker: AccountId) -> u64 {
+            self.nonces.get(&maker).unwrap_or(0)
+        }
+
+        /// Increment the caller's nonce, invalidating every order they signed or approved so far.
+        #[ink(message)]
+        pub fn increment_nonce(&mut self) {
+            let maker = self.env().caller();
+            let new_nonce = self.nonces(maker) + 1;
+            self.nonces.insert(&maker, &new_nonce);
+            EmitEvent::<WyvernExchange>::emit_event(self.env(), NonceIncremented { maker, new_nonce });
+        }
+
+        /// Approve an order and optionally mark it for orderbook inclusion. Must be called by the maker of the order.
+        ///param order Order to approve
+        ///param order_book_inclusion_desired Whether orderbook providers should include the order in their orderbooks
+        #[ink(message)]
+        pub fn approve_order(
+            &mut self,
+            order: Order,
+            order_book_inclusion_desired: bool,
+        ) -> Result<(), Error> {
+            // Assert sender is authorized to approve order.
+            if self.env().caller() != order.maker {
+                return Err(Error::NotMaker)
+            }
+
+            // Calculate order hash.
+            let hash = self.required_hash(&order);
+
+            // Assert order has not already been approved.
+            if self.approved_orders.get(&hash).unwrap_or(false) {
+                return Err(Error::AlreadyApproved)
+            }
+
+            // Mark order as approved.
+            self.approved_orders.insert(&hash, &true);
+
+            // Log approval event.
+            EmitEvent::<WyvernExchange>::emit_event(
+                self.env(),
+                OrderApproved {
+                    hash,
+                    maker: order.maker,
+                    order,
+                    order_book_inclusion_desired,
+                },
+            );
+            Ok(())
+        }
+
+        /// Returns whether the order with the given hash was approved on-chain by its maker.
+        #[ink(message)]
+        pub fn approved_orders(&self, hash: Hash) -> bool {
+            self.approved_orders.get(&hash).unwrap_or(false)
+        }
+
+        /// Cancel an order, preventing it from being matched. Must be called by the maker of the order.
+        ///param order Order to cancel
+        #[ink(message)]
+        pub fn cancel_order(&mut self, order: Order) -> Result<(), Error> {
+            // Calculate order hash.
+            let hash = self.required_hash(&order);
+
+            // Assert sender is authorized to cancel order.
+            if self.env().caller() != order.maker {
+                return Err(Error::NotMaker)
+            }
+
+            // Assert order is valid (which also rules out orders already cancelled or finalized).
+            self.validate_order(&hash, &order, &None)?;
+
+            // Mark order as cancelled, preventing it from being matched.
+            self.cancelled_or_finalized.insert(&hash, &true);
+
+            // Log cancel event.
+            EmitEvent::<WyvernExchange>::emit_event(self.env(), OrderCancelled { hash });
+            Ok(())
+        }
+
+        /// Returns whether the order with the given hash was cancelled or already matched.
+        #[ink(message)]
+        pub fn cancelled_or_finalized(&self, hash: Hash) -> bool {
+            self.cancelled_or_finalized.get(&hash).unwrap_or(false)
+        }
+
+        /// Calculate the settlement price of an order at the current block timestamp.
+        ///param side Order side
+        ///param sale_kind Method of sale
+        ///param base_price Order base price
+        ///param extra Order extra price data
+        ///param listing_time Order listing time
+        ///param expiration_time Order expiration time
+        ///return Current price of the order
+        #[ink(message)]
+        pub fn calculate_final_price(
+            &self,
+            side: Side,
+            sale_kind: SaleKind,
+            base_price: Balance,
+            extra: Balance,
+            listing_time: Timestamp,
+            expiration_time: Timestamp,
+        ) -> Result<Balance, Error> {
+            match sale_kind {
+                SaleKind::FixedPrice => Ok(base_price),
+                SaleKind::DutchAuction => {
+                    if !Self::validate_sale_kind_parameters(sale_kind, listing_time, expiration_time) {
+                        return Err(Error::InvalidSaleKind)
+                    }
+                    let now = self.env().block_timestamp();
+                    let elapsed = now.min(expiration_time).saturating_sub(listing_time);
+                    let duration = expiration_time - listing_time;
+                    let diff = extra
+                        .checked_mul(Balance::from(elapsed))
+                        .ok_or(Error::PriceOverflow)?
+                        / Balance::from(duration);
+                    match side {
+                        // Sell-side - start price: base_price. End price: base_price - extra.
+                        Side::Sell => base_price.checked_sub(diff).ok_or(Error::PriceOverflow),
+                        // Buy-side - start price: base_price. End price: base_price + extra.
+                        Side::Buy => base_price.checked_add(diff).ok_or(Error::PriceOverflow),
+                    }
+                }
+            }
+        }
+
+        /// Atomically match two orders, ensuring validity of the match, and execute all associated state transitions.
+        ///dev Reentrancy is rejected by the contracts pallet unless explicitly allowed by the callee
+        ///param buy Buy-side order
+        ///param buy_sig Buy-side order signature, `None` if the buyer submits the match or approved the order
+        ///param sell Sell-side order
+        ///param sell_sig Sell-side order signature, `None` if the seller submits the match or approved the order
+        ///param metadata Metadata topic of the `OrdersMatched` event, free for the caller to use
+        ///dev Orders paid in the native currency must transfer at least the amount due by the buyer; any excess is
+        ///    refunded to the caller
+        #[ink(message, payable)]
+        pub fn atomic_match(
+            &mut self,
+            buy: Order,
+            buy_sig: Option<Signature>,
+            sell: Order,
+            sell_sig: Option<Signature>,
+            metadata: Hash,
+        ) -> Result<(), Error> {
+            // Calldata replacement patterns are applied in place.
+            let (mut buy, mut sell) = (buy, sell);
+
+            // Ensure buy order validity.
+            let buy_hash = self.required_hash(&buy);
+            self.validate_order(&buy_hash, &buy, &buy_sig)?;
+
+            // Ensure sell order validity.
+            let sell_hash = self.required_hash(&sell);
+            self.validate_order(&sell_hash, &sell, &sell_sig)?;
+
+            // Must be matchable.
+            self.orders_can_match(&buy, &sell)?;
+
+            // Target must exist (prevent malicious terminations just prior to order settlement).
+            if !self.env().is_contract(&sell.target) {
+                return Err(Error::TargetNotContract)
+            }
+
+            // Must match calldata after replacement, if specified.
+            if !array_utils::order_calldata_can_match(
+                &mut buy.calldata,
+                &buy.replacement_pattern,
+                &mut sell.calldata,
+                &sell.replacement_pattern,
+            ) {
+                return Err(Error::CalldataMismatch)
+            }
+
+            // Mark both orders as finalized, before any external call is made.
+            self.cancelled_or_finalized.insert(&buy_hash, &true);
+            self.cancelled_or_finalized.insert(&sell_hash, &true);
+
+            // Transfer any payment and fees required by the match.
+            let price = self.execute_funds_transfer(&buy, &sell)?;
+
+            // Retrieve the seller's proxy.
+            let proxy = self.proxy_of(sell.maker).ok_or(Error::ProxyMissing)?;
+
+            // Execute the call through the proxy.
+            let mut proxy: AuthenticatedProxyRef = FromAccountId::from_account_id(proxy);
+            if !proxy.proxy(sell.target, sell.how_to_call, sell.calldata.clone()) {
+                return Err(Error::ProxyCallFailed)
+            }
+
+            // Static calls are intentionally done after the effectful call so they can check resulting state.
+
+            // Handle buy-side static call if specified.
+            if let Some(static_target) = buy.static_target {
+                if !self.static_call(static_target, &sell.calldata, &buy.static_extradata) {
+                    return Err(Error::StaticCallFailed)
+                }
+            }
+
+            // Handle sell-side static call if specified.
+            if let Some(static_target) = sell.static_target {
+                if !self.static_call(static_target, &sell.calldata, &sell.static_extradata) {
+                    return Err(Error::StaticCallFailed)
+                }
+            }
+
+            // Log match event.
+            let (maker, taker) = if sell.fee_recipient.is_some() {
+                (sell.maker, buy.maker)
+            } else {
+                (buy.maker, sell.maker)
+            };
+            EmitEvent::<WyvernExchange>::emit_event(
+                self.env(),
+                OrdersMatched {
+                    buy_hash,
+                    sell_hash,
+                    maker,
+                    taker,
+                    price,
+                    metadata,
+                },
+            );
+            Ok(())
+        }
+
+        /// Validate order parameters (does *not* check maker authorization).
+        fn validate_order_parameters(&self, order: &Order) -> Result<(), Error> {
+            // Order must be targeted at this protocol version (this Exchange contract).
+            if order.exchange != self.env().account_id() {
+                return Err(Error::WrongExchange)
+            }
+
+            // Order must possess valid sale kind parameter combination.
+            if !Self::validate_sale_kind_parameters(order.sale_kind, order.listing_time, order.expiration_time) {
+                return Err(Error::InvalidSaleKind)
+            }
+
+            // A static call must at least name the predicate message.
+            if order.static_target.is_some() && order.static_extradata.len() < 4 {
+                return Err(Error::InvalidStaticExtradata)
+            }
+
+            Ok(())
+        }
+
+        /// Validate a provided previously signed order, hash, and signature.
+        ///param hash Order hash (already calculated, passed to avoid recalculation)
+        ///param order Order to validate
+        ///param signature Maker signature over `hash`, if any
+        fn validate_order(
+            &self,
+            hash: &Hash,
+            order: &Order,
+            signature: &Option<Signature>,
+        ) -> Result<(), Error> {
+            // Order must have valid parameters.
+            self.validate_order_parameters(order)?;
+
+            // Order must have not been canceled or already filled.
+            if self.cancelled_or_finalized.get(hash).unwrap_or(false) {
+                return Err(Error::AlreadyFinalized)
+            }
+
+            // Order authentication. Order must be either:
+            // (a) sent by maker
+            if order.maker == self.env().caller() {
+                return Ok(())
+            }
+
+            // (b) previously approved
+            if self.approved_orders.get(hash).unwrap_or(false) {
+                return Ok(())
+            }
+
+            // (c) signed by maker
+            match signature {
+                Some(signature) if signature.verify(hash, &order.maker) => Ok(()),
+                _ => Err(Error::InvalidSignature),
+            }
+        }
+
+        /// Check whether two orders can be matched with each other by basic parameters (does not check order signatures / calldata).
+        fn orders_can_match(&self, buy: &Order, sell: &Order) -> Result<(), Error> {
+            // Must be opposite-side.
+            if buy.side != Side::Buy || sell.side != Side::Sell {
+                return Err(Error::SideMismatch)
+            }
+
+            // Must use same fee method.
+            if buy.fee_method != sell.fee_method {
+                return Err(Error::FeeMethodMismatch)
+            }
+
+            // Must use same payment token.
+            if buy.payment_token != sell.payment_token {
+                return Err(Error::PaymentTokenMismatch)
+            }
+
+            // Must match maker/taker addresses.
+            if sell.taker.is_some_and(|taker| taker != buy.maker)
+                || buy.taker.is_some_and(|taker| taker != sell.maker)
+            {
+                return Err(Error::TakerMismatch)
+            }
+
+            // One order must be maker and the other must be taker.
+            if buy.fee_recipient.is_some() == sell.fee_recipient.is_some() {
+                return Err(Error::FeeRecipientMismatch)
+            }
+
+            // Must match target and how_to_call.
+            if buy.target != sell.target || buy.how_to_call != sell.how_to_call {
+                return Err(Error::TargetMismatch)
+            }
+
+            // Both orders must be settleable.
+            let now = self.env().block_timestamp();
+            Self::can_settle_order(buy.listing_time, buy.expiration_time, now)?;
+            Self::can_settle_order(sell.listing_time, sell.expiration_time, now)
+        }
+
+        /// Calculate the price two orders would match at, if any.
+        ///param buy Buy-side order
+        ///param sell Sell-side order
+        ///return Match price, the price of the order setting the fees (the maker)
+        fn calculate_match_price(&self, buy: &Order, sell: &Order) -> Result<Balance, Error> {
+            // Calculate sell price.
+            let sell_price = self.calculate_current_price(sell)?;
+
+            // Calculate buy price.
+            let buy_price = self.calculate_current_price(buy)?;
+
+            // Require price cross.
+            if buy_price < sell_price {
+                return Err(Error::PriceMismatch)
+            }
+
+            // Maker/taker priority.
+            if sell.fee_recipient.is_some() {
+                Ok(sell_price)
+            } else {
+                Ok(buy_price)
+            }
+        }
+
+        /// Execute all payment and fee transfers associated with an order match.
+        ///dev The order which sets a fee recipient is the maker: its maker fees are charged to its maker. Under
+        ///    `FeeMethod::SplitFee` its taker fees are charged to the counterparty, which in turn caps them with the
+        ///    taker fees of its own order; under `FeeMethod::ProtocolFee` the taker fees are ignored.
+        ///    Precondition: orders_can_match passed, so exactly one order sets a fee recipient
+        ///param buy Buy-side order
+        ///param sell Sell-side order
+        ///return Match price
+        fn execute_funds_transfer(&self, buy: &Order, sell: &Order) -> Result<Balance, Error> {
+            // Only payable in the native currency.
+            if sell.payment_token.is_some() && self.env().transferred_value() != 0 {
+                return Err(Error::ValueMismatch)
+            }
+
+            // Calculate match price.
+            let price = self.calculate_match_price(buy, sell)?;
+
+            // If paying using a token, transfer tokens from buyer to seller.
+            if let Some(token) = sell.payment_token {
+                self.transfer_tokens(token, buy.maker, sell.maker, price)?;
+            }
+
+            // Amount that will be received by seller (for the native currency).
+            let mut receive_amount = price;
+
+            // Amount that must be sent by buyer (for the native currency).
+            let mut required_amount = price;
+
+            // Determine maker/taker and charge fees.
+            if let Some(fee_recipient) = sell.fee_recipient {
+                // Sell-side order is maker.
+                let maker_relayer_fee = Self::fee(sell.maker_relayer_fee, price)?;
+                let maker_protocol_fee = Self::fee(sell.maker_protocol_fee, price)?;
+                let (taker_relayer_fee, taker_protocol_fee) = Self::taker_fees(sell, buy, price)?;
+
+                match sell.payment_token {
+                    Some(token) => {
+                        // Maker fees are paid by the seller, taker fees by the buyer.
+                        self.transfer_tokens(token, sell.maker, fee_recipient, maker_relayer_fee)?;
+                        self.transfer_tokens(token, buy.maker, fee_recipient, taker_relayer_fee)?;
+                        self.transfer_tokens(token, sell.maker, self.protocol_fee_recipient, maker_protocol_fee)?;
+                        self.transfer_tokens(token, buy.maker, self.protocol_fee_recipient, taker_protocol_fee)?;
+                    }
+                    None => {
+                        // Maker fees are deducted from the amount the seller receives, taker fees are added to
+                        // the amount the buyer must send.
+                        let maker_fees = maker_relayer_fee
+                            .checked_add(maker_protocol_fee)
+                            .ok_or(Error::PriceOverflow)?;
+                        let taker_fees = taker_relayer_fee
+                            .checked_add(taker_protocol_fee)
+                            .ok_or(Error::PriceOverflow)?;
+                        receive_amount = receive_amount
+                            .checked_sub(maker_fees)
+                            .ok_or(Error::PriceOverflow)?;
+                        required_amount = required_amount
+                            .checked_add(taker_fees)
+                            .ok_or(Error::PriceOverflow)?;
+                        self.transfer_native(
+                            fee_recipient,
+                            maker_relayer_fee
+                                .checked_add(taker_relayer_fee)
+                                .ok_or(Error::PriceOverflow)?,
+                        )?;
+                        self.transfer_native(
+                            self.protocol_fee_recipient,
+                            maker_protocol_fee
+                                .checked_add(taker_protocol_fee)
+                                .ok_or(Error::PriceOverflow)?,
+                        )?;
+                    }
+                }
+            } else if let Some(fee_recipient) = buy.fee_recipient {
+                // Buy-side order is maker.
+                let maker_relayer_fee = Self::fee(buy.maker_relayer_fee, price)?;
+                let maker_protocol_fee = Self::fee(buy.maker_protocol_fee, price)?;
+                let (taker_relayer_fee, taker_protocol_fee) = Self::taker_fees(buy, sell, price)?;
+
+                // The exchange does not escrow the native currency, so buy-side fees require a payment token.
+                let token = sell.payment_token.ok_or(Error::UnsupportedPayment)?;
+
+                // Maker fees are paid by the buyer, taker fees by the seller.
+                self.transfer_tokens(token, buy.maker, fee_recipient, maker_relayer_fee)?;
+                self.transfer_tokens(token, sell.maker, fee_recipient, taker_relayer_fee)?;
+                self.transfer_tokens(token, buy.maker, self.protocol_fee_recipient, maker_protocol_fee)?;
+                self.transfer_tokens(token, sell.maker, self.protocol_fee_recipient, taker_protocol_fee)?;
+            }
+
+            if sell.payment_token.is_none() {
+                // Assert the buyer sent enough to pay for the order and the taker fees.
+                let value = self.env().transferred_value();
+                if value < required_amount {
+                    return Err(Error::InsufficientValue)
+                }
+
+                // Pay seller.
+                self.transfer_native(sell.maker, receive_amount)?;
+
+                // Refund any excess to the caller.
+                self.transfer_native(self.env().caller(), value - required_amount)?;
+            }
+
+            Ok(price)
+        }
+
+        /// Amount charged for a fee of `fee` basis points on a match at `price`.
+        fn fee(fee: Balance, price: Balance) -> Result<Balance, Error> {
+            price
+                .checked_mul(fee)
+                .map(|amount| amount / INVERSE_BASIS_POINT)
+                .ok_or(Error::PriceOverflow)
+        }
+
+        /// Relayer and protocol fees charged to the taker of a match at `price`, according to the maker's fee method.
+        ///param maker Order setting the fee recipient
+        ///param taker Counterparty order, whose taker fees are the maximum it accepts to pay
+        fn taker_fees(maker: &Order, taker: &Order, price: Balance) -> Result<(Balance, Balance), Error> {
+            match maker.fee_method {
+                // Only the maker pays fees.
+                FeeMethod::ProtocolFee => Ok((0, 0)),
+                FeeMethod::SplitFee => {
+                    // Assert taker fees are less than or equal to the maximum fees specified by the taker.
+                    if maker.taker_relayer_fee > taker.taker_relayer_fee
+                        || maker.taker_protocol_fee > taker.taker_protocol_fee
+                    {
+                        return Err(Error::FeeMismatch)
+                    }
+                    Ok((
+                        Self::fee(maker.taker_relayer_fee, price)?,
+                        Self::fee(maker.taker_protocol_fee, price)?,
+                    ))
+                }
+            }
+        }
+
+        /// Transfer tokens through the token transfer proxy, which the users approved once for all exchange versions.
+        ///param token Token to transfer
+        ///param from :AccountId to charge
+        ///param to :AccountId to credit
+        ///param amount Amount to transfer
+        fn transfer_tokens(
+            &self,
+            token: AccountId,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+        ) -> Result<(), Error> {
+            if amount == 0 {
+                return Ok(())
+            }
+            let mut token_transfer_proxy: WyvernTokenTransferProxyRef =
+                FromAccountId::from_account_id(self.token_transfer_proxy);
+            token_transfer_proxy
+                .transfer_from(token, from, to, amount)
+                .map_err(|_| Error::TransferFailed)
+        }
+
+        /// Transfer the native currency held by the exchange for the current match.
+        fn transfer_native(&self, to: AccountId, amount: Balance) -> Result<(), Error> {
+            if amount == 0 {
+                return Ok(())
+            }
+            self.env()
+                .transfer(to, amount)
+                .map_err(|_| Error::TransferFailed)
+        }
+
+        /// Calculate the current price of an order.
+        fn calculate_current_price(&self, order: &Order) -> Result<Balance, Error> {
+            self.calculate_final_price(
+                order.side,
+                order.sale_kind,
+                order.base_price,
+                order.extra,
+                order.listing_time,
+                order.expiration_time,
+            )
+        }
+
+        /// Check whether the parameter combination of a sale kind is valid.
+        ///dev A Dutch auction needs a finite lifetime for its price to move over
+        fn validate_sale_kind_parameters(
+            sale_kind: SaleKind,
+            listing_time: Timestamp,
+            expiration_time: Timestamp,
+        ) -> bool {
+            sale_kind == SaleKind::FixedPrice || listing_time < expiration_time
+        }
+
+        /// Check whether an order can be settled at `now`.
+        ///dev Precondition: parameters have passed validate_order_parameters
+        fn can_settle_order(
+            listing_time: Timestamp,
+            expiration_time: Timestamp,
+            now: Timestamp,
+        ) -> Result<(), Error> {
+            if listing_time >= now {
+                return Err(Error::NotYetListed)
+            }
+            if expiration_time != 0 && now >= expiration_time {
+                return Err(Error::Expired)
+            }
+            Ok(())
+        }
+
+        /// Execute a static call to a predicate and return whether it approved the call.
+        ///dev Reentry into the exchange is refused by default and the call is bounded by `STATIC_CALL_GAS_LIMIT`.
+        ///    The contracts pallet has no read-only call flag, so the predicate is trusted not to change state;
+        ///    makers should only name predicates they have audited.
+        ///param target Predicate contract
+        ///param calldata Merged calldata of the matched orders, appended as the last argument
+        ///param extradata Selector and leading arguments of the predicate message
+        ///return Whether the predicate returned `true`; a failing or trapping predicate counts as `false`
+        fn static_call(&self, target: AccountId, calldata: &[u8], extradata: &[u8]) -> bool {
+            let mut selector = [0u8; 4];
+            selector.copy_from_slice(&extradata[..4]);
+            build_call::<<Self as ::ink_lang::reflect::ContractEnv>::Env>()
+                .call_type(
+                    Call::new()
+                        .callee(target)
+                        .gas_limit(STATIC_CALL_GAS_LIMIT)
+                        .transferred_value(0),
+                )
+                .exec_input(
+                    ExecutionInput::new(selector.into())
+                        .push_arg(CallInput(&extradata[4..]))
+                        .push_arg(calldata),
+                )
+                .returns::<bool>()
+                .fire()
+                .unwrap_or(false)
+        }
+
+        /// Hash an order with the current nonce of its maker, returning the hash that the maker must sign.
+        fn required_hash(&self, order: &Order) -> Hash {
+            order_hash::hash_to_sign(&self.domain_separator(), order, self.nonces(order.maker))
+        }
+
+        /// Proxy registered by `user` in the proxy registry, if any.
+        fn proxy_of(&self, user: AccountId) -> Option<AccountId> {
+            build_call::<<Self as ::ink_lang::reflect::ContractEnv>::Env>()
+                .call_type(Call::new().callee(self.registry).gas_limit(0).transferred_value(0))
+                .exec_input(ExecutionInput::new(REGISTRY_PROXIES_SELECTOR.into()).push_arg(user))
+                .returns::<Option<AccountId>>()
+                .fire()
+                .unwrap_or(None)
+        }
+    }
+
+    /// Unit tests in Rust are normally defined within such a `#[cfg(test)]`
+    /// module and test functions are marked with a `#[test]` attribute.
+    /// The below code is technically just normal Rust code.
+    #[cfg(test)]
+    mod tests {
+        /// Imports all the definitions from the outer scope so we can use them here.
+        use super::*;
+
+        /// Imports `ink_lang` so we can use `#[ink::test]`.
+        use ink_lang as ink;
+
+        const EXCHANGE: [u8; 32] = [7; 32];
+
+        fn default_accounts() -> ink_env::test::DefaultAccounts<Environment> {
+            ink_env::test::default_accounts::<Environment>()
+        }
+
+        fn balance_of(account: AccountId) -> Balance {
+            ink_env::test::get_account_balance::<Environment>(account)
+                .expect("account has a balance")
+        }
+
+        fn balance_of_or_zero(account: AccountId) -> Balance {
+            ink_env::test::get_account_balance::<Environment>(account).unwrap_or(0)
+        }
+
+        /// Instantiate the exchange at `EXCHANGE`, with eve as the protocol fee recipient.
+        fn build_contract() -> WyvernExchange {
+            let accounts = default_accounts();
+            ink_env::test::set_callee::<Environment>(AccountId::from(EXCHANGE));
+            WyvernExchange::new(accounts.django, accounts.frank, accounts.eve, Hash::default())
+        }
+
+        /// Call the exchange as `caller`, transferring `value` along with the call.
+        ///dev The off-chain environment does not move the value, so the exchange is credited here
+        fn call_with_value(caller: AccountId, value: Balance) {
+            let exchange = AccountId::from(EXCHANGE);
+            ink_env::test::set_caller::<Environment>(caller);
+            ink_env::test::set_account_balance::<Environment>(
+                exchange,
+                balance_of_or_zero(exchange) + value,
+            );
+            ink_env::test::set_value_transferred::<Environment>(value);
+        }
+
+        /// A fixed price order paid in the native currency.
+        fn order(side: Side, maker: AccountId, fee_recipient: Option<AccountId>, base_price: Balance) -> Order {
+            let accounts = default_accounts();
+            Order {
+                exchange: AccountId::from(EXCHANGE),
+                maker,
+                taker: None,
+                maker_relayer_fee: 500,
+                taker_relayer_fee: 0,
+                maker_protocol_fee: 100,
+                taker_protocol_fee: 0,
+                fee_recipient,
+                fee_method: FeeMethod::SplitFee,
+                side,
+                sale_kind: SaleKind::FixedPrice,
+                target: accounts.frank,
+                how_to_call: HowToCall::Call,
+                calldata: Vec::new(),
+                replacement_pattern: Vec::new(),
+                static_target: None,
+                static_extradata: Vec::new(),
+                payment_token: None,
+                base_price,
+                extra: 0,
+                listing_time: 0,
+                expiration_time: 0,
+                salt: 0,
+            }
+        }
+
+        #[ink::test]
+        fn native_overpayment_is_refunded() {
+            let accounts = default_accounts();
+            let exchange = build_contract();
+            let sell = order(Side::Sell, accounts.alice, Some(accounts.charlie), 100);
+            let buy = order(Side::Buy, accounts.bob, None, 100);
+            let (seller, relayer, protocol, buyer) = (
+                balance_of(accounts.alice),
+                balance_of_or_zero(accounts.charlie),
+                balance_of_or_zero(accounts.eve),
+                balance_of(accounts.bob),
+            );
+
+            call_with_value(accounts.bob, 150);
+            assert_eq!(exchange.execute_funds_transfer(&buy, &sell), Ok(100));
+
+            // The seller receives the price minus the 5% relayer and 1% protocol maker fees.
+            assert_eq!(balance_of(accounts.alice), seller + 94);
+            assert_eq!(balance_of(accounts.charlie), relayer + 5);
+            assert_eq!(balance_of(accounts.eve), protocol + 1);
+            // The 50 sent on top of the price are refunded to the caller.
+            assert_eq!(balance_of(accounts.bob), buyer + 50);
+            assert_eq!(balance_of(AccountId::from(EXCHANGE)), 0);
+        }
+
+        #[ink::test]
+        fn native_exact_payment_leaves_nothing_to_refund() {
+            let accounts = default_accounts();
+            let exchange = build_contract();
+            let sell = order(Side::Sell, accounts.alice, Some(accounts.charlie), 100);
+            let buy = order(Side::Buy, accounts.bob, None, 100);
+            let buyer = balance_of(accounts.bob);
+
+            call_with_value(accounts.bob, 100);
+            assert_eq!(exchange.execute_funds_transfer(&buy, &sell), Ok(100));
+
+            assert_eq!(balance_of(accounts.bob), buyer);
+            assert_eq!(balance_of(AccountId::from(EXCHANGE)), 0);
+        }
+
+        #[ink::test]
+        fn native_underpayment_fails() {
+            let accounts = default_accounts();
+            let exchange = build_contract();
+            let mut sell = order(Side::Sell, accounts.alice, Some(accounts.charlie), 100);
+            // A 2% taker relayer fee is due on top of the price.
+            sell.taker_relayer_fee = 200;
+            let mut buy = order(Side::Buy, accounts.bob, None, 100);
+            buy.taker_relayer_fee = 200;
+
+            call_with_value(accounts.bob, 101);
+            assert_eq!(
+                exchange.execute_funds_transfer(&buy, &sell),
+                Err(Error::InsufficientValue)
+            );
+        }
+
+        #[ink::test]
+        fn value_is_rejected_for_token_payments() {
+            let accounts = default_accounts();
+            let exchange = build_contract();
+            let mut sell = order(Side::Sell, accounts.alice, Some(accounts.charlie), 100);
+            sell.payment_token = Some(accounts.frank);
+            let mut buy = order(Side::Buy, accounts.bob, None, 100);
+            buy.payment_token = Some(accounts.frank);
+
+            call_with_value(accounts.bob, 100);
+            assert_eq!(
+                exchange.execute_funds_transfer(&buy, &sell),
+                Err(Error::ValueMismatch)
+            );
+        }
+
+        #[ink::test]
+        fn hash_messages_match_shared_crate() {
+            let accounts = default_accounts();
+            let exchange = build_contract();
+            let sell = order(Side::Sell, accounts.alice, Some(accounts.charlie), 100);
+            let domain = order_hash::DomainSeparator::new(Hash::default(), AccountId::from(EXCHANGE));
+
+            assert_eq!(exchange.domain_separator(), domain);
+            assert_eq!(exchange.hash_order(sell.clone()), order_hash::hash_order(&sell, 0));
+            assert_eq!(
+                exchange.hash_to_sign(sell.clone()),
+                order_hash::hash_to_sign(&domain, &sell, 0)
+            );
+        }
+
+        #[ink::test]
+        fn approve_order_requires_maker() {
+            let accounts = default_accounts();
+            let mut exchange = build_contract();
+            let sell = order(Side::Sell, accounts.alice, Some(accounts.charlie), 100);
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(exchange.approve_order(sell, false), Err(Error::NotMaker));
+        }
+
+        #[ink::test]
+        fn approved_order_validates_without_signature() {
+            let accounts = default_accounts();
+            let mut exchange = build_contract();
+            let sell = order(Side::Sell, accounts.alice, Some(accounts.charlie), 100);
+            let hash = exchange.hash_to_sign(sell.clone());
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(exchange.validate_order(&hash, &sell, &None), Err(Error::InvalidSignature));
+
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(exchange.approve_order(sell.clone(), true), Ok(()));
+            assert!(exchange.approved_orders(hash));
+            assert_eq!(exchange.approve_order(sell.clone(), true), Err(Error::AlreadyApproved));
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(exchange.validate_order(&hash, &sell, &None), Ok(()));
+        }
+
+        #[ink::test]
+        fn cancel_order_requires_maker() {
+            let accounts = default_accounts();
+            let mut exchange = build_contract();
+            let sell = order(Side::Sell, accounts.alice, Some(accounts.charlie), 100);
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(exchange.cancel_order(sell), Err(Error::NotMaker));
+        }
+
+        #[ink::test]
+        fn cancelled_order_is_finalized() {
+            let accounts = default_accounts();
+            let mut exchange = build_contract();
+            let sell = order(Side::Sell, accounts.alice, Some(accounts.charlie), 100);
+            let hash = exchange.hash_to_sign(sell.clone());
+
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(exchange.cancel_order(sell.clone()), Ok(()));
+            assert!(exchange.cancelled_or_finalized(hash));
+
+            assert_eq!(exchange.cancel_order(sell.clone()), Err(Error::AlreadyFinalized));
+            assert_eq!(exchange.validate_order(&hash, &sell, &None), Err(Error::AlreadyFinalized));
+        }
+
+        #[ink::test]
+        fn increment_nonce_invalidates_approvals() {
+            let accounts = default_accounts();
+            let mut exchange = build_contract();
+            let sell = order(Side::Sell, accounts.alice, Some(accounts.charlie), 100);
+
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(exchange.approve_order(sell.clone(), false), Ok(()));
+            let approved_hash = exchange.hash_to_sign(sell.clone());
+
+            exchange.increment_nonce();
+            assert_eq!(exchange.nonces(accounts.alice), 1);
+            let hash = exchange.hash_to_sign(sell.clone());
+            assert_ne!(hash, approved_hash);
+            assert!(!exchange.approved_orders(hash));
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(exchange.validate_order(&hash, &sell, &None), Err(Error::InvalidSignature));
+        }
+
+        /// Time between two blocks of the off-chain environment, which starts at timestamp 0.
+        const BLOCK_TIME: Timestamp = 6;
+
+        fn advance_blocks(blocks: u32) {
+            for _ in 0..blocks {
+                ink_env::test::advance_block::<Environment>();
+            }
+        }
+
+        #[ink::test]
+        fn dutch_auction_price_follows_block_timestamp() {
+            let exchange = build_contract();
+            let (listing_time, expiration_time) = (10 * BLOCK_TIME, 20 * BLOCK_TIME);
+            let price = |side| {
+                exchange.calculate_final_price(
+                    side,
+                    SaleKind::DutchAuction,
+                    1_000,
+                    600,
+                    listing_time,
+                    expiration_time,
+                )
+            };
+
+            // Before listing the auction has not started.
+            assert_eq!(price(Side::Sell), Ok(1_000));
+            assert_eq!(price(Side::Buy), Ok(1_000));
+
+            // Half way through, half of `extra` is taken off (sell) or added (buy).
+            advance_blocks(15);
+            assert_eq!(price(Side::Sell), Ok(700));
+            assert_eq!(price(Side::Buy), Ok(1_300));
+
+            // After expiry the price stays at its final value.
+            advance_blocks(10);
+            assert_eq!(price(Side::Sell), Ok(400));
+            assert_eq!(price(Side::Buy), Ok(1_600));
+        }
+
+        #[ink::test]
+        fn dutch_auction_sell_price_cannot_underflow() {
+            let exchange = build_contract();
+            advance_blocks(10);
+            assert_eq!(
+                exchange.calculate_final_price(Side::Sell, SaleKind::DutchAuction, 100, 500, 0, 10 * BLOCK_TIME),
+                Err(Error::PriceOverflow)
+            );
+        }
+
+        #[ink::test]
+        fn dutch_auction_needs_expiration_after_listing() {
+            let exchange = build_contract();
+            assert_eq!(
+                exchange.calculate_final_price(Side::Sell, SaleKind::DutchAuction, 1_000, 500, 100, 100),
+                Err(Error::InvalidSaleKind)
+            );
+            assert_eq!(
+                exchange.calculate_final_price(Side::Sell, SaleKind::DutchAuction, 1_000, 500, 100, 0),
+                Err(Error::InvalidSaleKind)
+            );
+        }
+
+        #[ink::test]
+        fn fixed_price_ignores_block_timestamp() {
+            let exchange = build_contract();
+            advance_blocks(50);
+            assert_eq!(
+                exchange.calculate_final_price(Side::Sell, SaleKind::FixedPrice, 1_000, 500, 100, 200),
+                Ok(1_000)
+            );
+        }
+
+        #[ink::test]
+        fn orders_must_be_on_opposite_sides() {
+            let accounts = default_accounts();
+            let exchange = build_contract();
+            let sell = order(Side::Sell, accounts.alice, Some(accounts.charlie), 100);
+            let buy = order(Side::Sell, accounts.bob, None, 100);
+            assert_eq!(exchange.orders_can_match(&buy, &sell), Err(Error::SideMismatch));
+        }
+
+        #[ink::test]
+        fn orders_must_name_each_other_as_taker() {
+            let accounts = default_accounts();
+            let exchange = build_contract();
+            let mut sell = order(Side::Sell, accounts.alice, Some(accounts.charlie), 100);
+            let buy = order(Side::Buy, accounts.bob, None, 100);
+
+            // Orders listed at timestamp 0 can only be settled from the next block on.
+            advance_blocks(1);
+            sell.taker = Some(accounts.bob);
+            assert_eq!(exchange.orders_can_match(&buy, &sell), Ok(()));
+            sell.taker = Some(accounts.django);
+            assert_eq!(exchange.orders_can_match(&buy, &sell), Err(Error::TakerMismatch));
+        }
+
+        #[ink::test]
+        fn exactly_one_order_sets_a_fee_recipient() {
+            let accounts = default_accounts();
+            let exchange = build_contract();
+            let sell = order(Side::Sell, accounts.alice, Some(accounts.charlie), 100);
+            let buy = order(Side::Buy, accounts.bob, Some(accounts.charlie), 100);
+            assert_eq!(exchange.orders_can_match(&buy, &sell), Err(Error::FeeRecipientMismatch));
+
+            let sell = order(Side::Sell, accounts.alice, None, 100);
+            let buy = order(Side::Buy, accounts.bob, None, 100);
+            assert_eq!(exchange.orders_can_match(&buy, &sell), Err(Error::FeeRecipientMismatch));
+        }
+
+        #[ink::test]
+        fn orders_must_be_listed_and_unexpired() {
+            let accounts = default_accounts();
+            let exchange = build_contract();
+            let mut sell = order(Side::Sell, accounts.alice, Some(accounts.charlie), 100);
+            sell.listing_time = 10 * BLOCK_TIME;
+            sell.expiration_time = 20 * BLOCK_TIME;
+            let buy = order(Side::Buy, accounts.bob, None, 100);
+
+            // Not listed before, nor at, the listing time.
+            advance_blocks(10);
+            assert_eq!(exchange.orders_can_match(&buy, &sell), Err(Error::NotYetListed));
+            advance_blocks(1);
+            assert_eq!(exchange.orders_can_match(&buy, &sell), Ok(()));
+            // Expired from the expiration time on.
+            advance_blocks(9);
+            assert_eq!(exchange.orders_can_match(&buy, &sell), Err(Error::Expired));
+        }
+
+        #[ink::test]
+        fn buy_price_must_cover_sell_price() {
+            let accounts = default_accounts();
+            let exchange = build_contract();
+            let sell = order(Side::Sell, accounts.alice, Some(accounts.charlie), 100);
+            let buy = order(Side::Buy, accounts.bob, None, 99);
+            assert_eq!(exchange.calculate_match_price(&buy, &sell), Err(Error::PriceMismatch));
+
+            // The maker's price is used when the prices cross.
+            let buy = order(Side::Buy, accounts.bob, None, 120);
+            assert_eq!(exchange.calculate_match_price(&buy, &sell), Ok(100));
+        }
+
+        #[ink::test]
+        fn taker_fees_are_capped_by_counterparty() {
+            let accounts = default_accounts();
+            let exchange = build_contract();
+            let mut sell = order(Side::Sell, accounts.alice, Some(accounts.charlie), 100);
+            sell.taker_relayer_fee = 200;
+            let mut buy = order(Side::Buy, accounts.bob, None, 100);
+            buy.taker_relayer_fee = 100;
+
+            call_with_value(accounts.bob, 102);
+            assert_eq!(exchange.execute_funds_transfer(&buy, &sell), Err(Error::FeeMismatch));
+        }
+
+        #[ink::test]
+        fn buy_side_fees_need_a_payment_token() {
+            let accounts = default_accounts();
+            let exchange = build_contract();
+            let sell = order(Side::Sell, accounts.alice, None, 100);
+            let buy = order(Side::Buy, accounts.bob, Some(accounts.charlie), 100);
+
+            call_with_value(accounts.bob, 100);
+            assert_eq!(exchange.execute_funds_transfer(&buy, &sell), Err(Error::UnsupportedPayment));
+        }
+
+        #[ink::test]
+        fn native_fees_cannot_overflow() {
+            let accounts = default_accounts();
+            let exchange = build_contract();
+            let mut sell = order(Side::Sell, accounts.alice, Some(accounts.charlie), 100);
+            sell.fee_method = FeeMethod::ProtocolFee;
+            sell.maker_relayer_fee = Balance::MAX;
+            sell.maker_protocol_fee = 1;
+            let mut buy = order(Side::Buy, accounts.bob, None, 100);
+            buy.fee_method = FeeMethod::ProtocolFee;
+
+            call_with_value(accounts.bob, 0);
+            assert_eq!(exchange.execute_funds_transfer(&buy, &sell), Err(Error::PriceOverflow));
+        }
+
+        /// Matching orders at 1000, the sell order charging 5% relayer and 1% protocol maker fees and 2% relayer
+        /// and 1% protocol taker fees, the buy order accepting no taker fees.
+        fn order_with_fees(fee_method: FeeMethod) -> (Order, Order) {
+            let accounts = default_accounts();
+            let mut sell = order(Side::Sell, accounts.alice, Some(accounts.charlie), 1_000);
+            sell.fee_method = fee_method;
+            sell.taker_relayer_fee = 200;
+            sell.taker_protocol_fee = 100;
+            let mut buy = order(Side::Buy, accounts.bob, None, 1_000);
+            buy.fee_method = fee_method;
+            (buy, sell)
+        }
+
+        #[ink::test]
+        fn split_fee_charges_maker_and_taker() {
+            let accounts = default_accounts();
+            let exchange = build_contract();
+            let (mut buy, sell) = order_with_fees(FeeMethod::SplitFee);
+            buy.taker_relayer_fee = 200;
+            buy.taker_protocol_fee = 100;
+            let (seller, relayer, protocol) = (
+                balance_of(accounts.alice),
+                balance_of_or_zero(accounts.charlie),
+                balance_of_or_zero(accounts.eve),
+            );
+
+            call_with_value(accounts.bob, 1_030);
+            assert_eq!(exchange.execute_funds_transfer(&buy, &sell), Ok(1_000));
+
+            assert_eq!(balance_of(accounts.alice), seller + 940);
+            assert_eq!(balance_of(accounts.charlie), relayer + 50 + 20);
+            assert_eq!(balance_of(accounts.eve), protocol + 10 + 10);
+            assert_eq!(balance_of(AccountId::from(EXCHANGE)), 0);
+        }
+
+        #[ink::test]
+        fn split_fee_taker_fees_must_be_accepted() {
+            let accounts = default_accounts();
+            let exchange = build_contract();
+            let (buy, sell) = order_with_fees(FeeMethod::SplitFee);
+
+            call_with_value(accounts.bob, 1_030);
+            assert_eq!(exchange.execute_funds_transfer(&buy, &sell), Err(Error::FeeMismatch));
+        }
+
+        #[ink::test]
+        fn protocol_fee_charges_maker_only() {
+            let accounts = default_accounts();
+            let exchange = build_contract();
+            // The buyer accepts no taker fees, which the protocol fee method does not charge anyway.
+            let (buy, sell) = order_with_fees(FeeMethod::ProtocolFee);
+            let (seller, relayer, protocol, buyer) = (
+                balance_of(accounts.alice),
+                balance_of_or_zero(accounts.charlie),
+                balance_of_or_zero(accounts.eve),
+                balance_of(accounts.bob),
+            );
+
+            call_with_value(accounts.bob, 1_000);
+            assert_eq!(exchange.execute_funds_transfer(&buy, &sell), Ok(1_000));
+
+            assert_eq!(balance_of(accounts.alice), seller + 940);
+            assert_eq!(balance_of(accounts.charlie), relayer + 50);
+            assert_eq!(balance_of(accounts.eve), protocol + 10);
+            assert_eq!(balance_of(accounts.bob), buyer);
+            assert_eq!(balance_of(AccountId::from(EXCHANGE)), 0);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use ink_env::{
+    hash::{
+        Blake2x256,
+        HashOutput,
+    },
+    AccountId,
+    Hash,
+};
+use scale::{
+    Decode,
+    Encode,
+};
+
+/// Signing context used by Substrate for sr25519 signatures.
+const SR25519_SIGNING_CONTEXT: &[u8] = b"substrate";
+
+/// Signature of an order maker over the hash returned by `hash_to_sign`.
+///
+/// The variants cover the key types of Substrate accounts, so that makers can sign
+/// with whichever wallet they use.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum Signature {
+    /// Schnorr signature over Ristretto25519; the signer is the 32 byte public key.
+    Sr25519([u8; 64]),
+    /// Ed25519 signature; the signer is the 32 byte public key.
+    Ed25519([u8; 64]),
+    /// Recoverable secp256k1 ECDSA signature; the signer is the Blake2-256 hash of the
+    /// 33 byte compressed public key.
+    Ecdsa([u8; 65]),
+}
+
+impl Signature {
+    /// Returns whether this is a valid signature of `hash` by `signer`.
+    pub fn verify(&self, hash: &Hash, signer: &AccountId) -> bool {
+        let mut message = [0u8; 32];
+        message.copy_from_slice(&hash.encode());
+        let public = signer.encode();
+        match self {
+            Signature::Sr25519(signature) => verify_sr25519(signature, &message, &public),
+            Signature::Ed25519(signature) => verify_ed25519(signature, &message, &public),
+            Signature::Ecdsa(signature) => verify_ecdsa(signature, &message, signer),
+        }
+    }
+}
+
+fn verify_sr25519(signature: &[u8; 64], message: &[u8; 32], public: &[u8]) -> bool {
+    let public = match schnorrkel::PublicKey::from_bytes(public) {
+        Ok(public) => public,
+        Err(_) => return false,
+    };
+    let signature = match schnorrkel::Signature::from_bytes(signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+    public
+        .verify_simple(SR25519_SIGNING_CONTEXT, message, &signature)
+        .is_ok()
+}
+
+fn verify_ed25519(signature: &[u8; 64], message: &[u8; 32], public: &[u8]) -> bool {
+    use ed25519_dalek::Verifier;
+
+    let public = match ed25519_dalek::PublicKey::from_bytes(public) {
+        Ok(public) => public,
+        Err(_) => return false,
+    };
+    let signature = match ed25519_dalek::Signature::from_bytes(signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+    public.verify(message, &signature).is_ok()
+}
+
+fn verify_ecdsa(signature: &[u8; 65], message: &[u8; 32], signer: &AccountId) -> bool {
+    let mut public = [0u8; 33];
+    if ink_env::ecdsa_recover(signature, message, &mut public).is_err() {
+        return false
+    }
+    let mut account = <Blake2x256 as HashOutput>::Type::default();
+    ink_env::hash_bytes::<Blake2x256>(&public, &mut account);
+    AccountId::from(account) == *signer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Recoverable signature of `ECDSA_MESSAGE` by the secp256k1 secret key `[0x11; 32]`, and the
+    // Blake2-256 hash of its compressed public key, computed outside of this crate.
+    const ECDSA_MESSAGE: [u8; 32] = [
+        0xdc, 0x67, 0x70, 0x21, 0x36, 0xef, 0x99, 0xc6,
+        0x50, 0xe9, 0x9d, 0xdc, 0x15, 0x8b, 0xcb, 0x9a,
+        0x80, 0xcd, 0xad, 0x4e, 0x4d, 0x4d, 0x33, 0x50,
+        0x8f, 0x74, 0x34, 0xa8, 0xeb, 0x96, 0xb6, 0xe1,
+    ];
+    const ECDSA_SIGNATURE: [u8; 65] = [
+        0x46, 0x6d, 0x7f, 0xca, 0xe5, 0x63, 0xe5, 0xcb,
+        0x09, 0xa0, 0xd1, 0x87, 0x0b, 0xb5, 0x80, 0x34,
+        0x48, 0x04, 0x61, 0x78, 0x79, 0xa1, 0x49, 0x49,
+        0xcf, 0x22, 0x28, 0x5f, 0x1b, 0xae, 0x3f, 0x27,
+        0x55, 0xaf, 0x6d, 0xb1, 0x5e, 0x07, 0x98, 0x49,
+        0xb2, 0x47, 0xe3, 0xa0, 0x88, 0xf2, 0x0a, 0x66,
+        0x88, 0x7c, 0xd7, 0x15, 0xe2, 0x2c, 0x9f, 0xb5,
+        0xe7, 0xb8, 0xf0, 0xd6, 0xed, 0x62, 0x57, 0xde,
+        0x00,
+    ];
+    const ECDSA_SIGNER: [u8; 32] = [
+        0x2d, 0x95, 0xeb, 0xcd, 0xc7, 0xd1, 0xe5, 0x28,
+        0xeb, 0x8d, 0xc3, 0x39, 0xa5, 0xc4, 0x47, 0x21,
+        0xd3, 0x97, 0x43, 0x93, 0x2b, 0x82, 0xa3, 0x30,
+        0x3f, 0x5a, 0x22, 0xac, 0x1e, 0x47, 0x0d, 0x36,
+    ];
+
+    fn message() -> [u8; 32] {
+        [9; 32]
+    }
+
+    fn tampered(hash: &Hash) -> Hash {
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(&hash.encode());
+        bytes[0] ^= 1;
+        Hash::from(bytes)
+    }
+
+    fn sr25519_keypair() -> schnorrkel::Keypair {
+        schnorrkel::MiniSecretKey::from_bytes(&[1; 32])
+            .expect("valid seed")
+            .expand_to_keypair(schnorrkel::ExpansionMode::Ed25519)
+    }
+
+    fn ed25519_keypair() -> ed25519_dalek::Keypair {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[2; 32]).expect("valid seed");
+        let public = ed25519_dalek::PublicKey::from(&secret);
+        ed25519_dalek::Keypair { secret, public }
+    }
+
+    fn sign_sr25519() -> (Signature, AccountId) {
+        let keypair = sr25519_keypair();
+        let signature = keypair.sign_simple(SR25519_SIGNING_CONTEXT, &message());
+        (
+            Signature::Sr25519(signature.to_bytes()),
+            AccountId::from(keypair.public.to_bytes()),
+        )
+    }
+
+    fn sign_ed25519() -> (Signature, AccountId) {
+        use ed25519_dalek::Signer;
+
+        let keypair = ed25519_keypair();
+        let signature = keypair.sign(&message());
+        (
+            Signature::Ed25519(signature.to_bytes()),
+            AccountId::from(keypair.public.to_bytes()),
+        )
+    }
+
+    #[test]
+    fn sr25519_signature_verifies() {
+        let (signature, signer) = sign_sr25519();
+        let hash = Hash::from(message());
+        assert!(signature.verify(&hash, &signer));
+        assert!(!signature.verify(&tampered(&hash), &signer));
+        assert!(!signature.verify(&hash, &sign_ed25519().1));
+    }
+
+    #[test]
+    fn ed25519_signature_verifies() {
+        let (signature, signer) = sign_ed25519();
+        let hash = Hash::from(message());
+        assert!(signature.verify(&hash, &signer));
+        assert!(!signature.verify(&tampered(&hash), &signer));
+        assert!(!signature.verify(&hash, &sign_sr25519().1));
+    }
+
+    #[test]
+    fn ecdsa_signature_verifies() {
+        let signature = Signature::Ecdsa(ECDSA_SIGNATURE);
+        let hash = Hash::from(ECDSA_MESSAGE);
+        let signer = AccountId::from(ECDSA_SIGNER);
+        assert!(signature.verify(&hash, &signer));
+        assert!(!signature.verify(&tampered(&hash), &signer));
+        assert!(!signature.verify(&hash, &sign_ed25519().1));
+    }
+
+    #[test]
+    fn signature_of_wrong_variant_fails() {
+        let hash = Hash::from(message());
+        let (signature, signer) = sign_sr25519();
+        assert!(!Signature::Ed25519(schnorr_bytes(signature)).verify(&hash, &signer));
+
+        let (signature, signer) = sign_ed25519();
+        assert!(!Signature::Sr25519(schnorr_bytes(signature)).verify(&hash, &signer));
+    }
+
+    /// The 64 signature bytes of an sr25519 or ed25519 signature.
+    fn schnorr_bytes(signature: Signature) -> [u8; 64] {
+        match signature {
+            Signature::Sr25519(bytes) | Signature::Ed25519(bytes) => bytes,
+            Signature::Ecdsa(_) => unreachable!("not a 64 byte signature"),
+        }
+    }
+}
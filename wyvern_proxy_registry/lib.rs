@@ -1,10 +1,15 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
+pub use self::wyvern_proxy_registry::{
+    WyvernProxyRegistry,
+    WyvernProxyRegistryRef,
+};
 use ink_lang as ink;
 
 #[ink::contract]
 mod wyvern_proxy_registry {
     use authenticated_proxy::AuthenticatedProxyRef;
+    use ink_lang::ToAccountId;
     use ownable_delegate_proxy::OwnableDelegateProxyRef;
 
     //  Delay period for adding an authenticated contract.
@@ -84,6 +89,22 @@ mod wyvern_proxy_registry {
             // contracts[auth_address] = true;
             self.contracts.insert(&auth_address, &true);
         }
+
+        /// Returns the proxy registered by `user`, if any.
+        ///dev Used by the exchange to find the AuthenticatedProxy acting on behalf of an order maker
+        ///param user :AccountId of the user whose proxy is requested
+        #[ink(message)]
+        pub fn proxies(&self, user: AccountId) -> Option<AccountId> {
+            self.proxies.get(&user).map(|proxy| proxy.to_account_id())
+        }
+
+        /// Returns whether `addr` is allowed to call the proxies.
+        ///dev Used by the token transfer proxy to authenticate exchanges
+        ///param addr :AccountId of the contract to check
+        #[ink(message)]
+        pub fn contracts(&self, addr: AccountId) -> bool {
+            self.contracts.get(&addr).unwrap_or(false)
+        }
     }
 
     impl ProxyRegistry for WyvernProxyRegistry {
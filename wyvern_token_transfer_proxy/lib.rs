@@ -1,75 +1,407 @@
+//   Token transfer proxy. Users approve this contract once for their tokens; every exchange version
+//   authenticated by the WyvernProxyRegistry can then move those tokens on their behalf.
+
 #![cfg_attr(not(feature = "std"), no_std)]
 
+pub use self::wyvern_token_transfer_proxy::{
+    Error,
+    Id,
+    PSP22Error,
+    PSP34Error,
+    PSP37Error,
+    WyvernTokenTransferProxy,
+    WyvernTokenTransferProxyRef,
+};
+
 use ink_lang as ink;
 
 #[ink::contract]
 mod wyvern_token_transfer_proxy {
+    use ink_env::{
+        call::{
+            build_call,
+            Call,
+            ExecutionInput,
+        },
+        ReturnFlags,
+    };
+    use ink_prelude::{
+        string::String,
+        vec::Vec,
+    };
+
+    /// Delay before a registry change takes effect, in milliseconds (two weeks).
+    ///dev Gives users time to revoke their approvals if a malicious registry is scheduled, in line with the
+    ///    registry's own delay for authenticating contracts
+    const DELAY_PERIOD: Timestamp = 14 * 24 * 60 * 60 * 1000;
+
+    /// Selector of `WyvernProxyRegistry::contracts`.
+    const REGISTRY_CONTRACTS_SELECTOR: [u8; 4] = [0x17, 0x1b, 0xa2, 0x92];
+
+    /// Selector of `PSP22::transfer_from`.
+    const PSP22_TRANSFER_FROM_SELECTOR: [u8; 4] = [0x54, 0xb3, 0xc7, 0x6e];
+
+    /// Selector of `PSP34::owner_of`.
+    const PSP34_OWNER_OF_SELECTOR: [u8; 4] = [0x11, 0x68, 0x62, 0x4d];
+
+    /// Selector of `PSP34::transfer`.
+    const PSP34_TRANSFER_SELECTOR: [u8; 4] = [0x31, 0x28, 0xd6, 0x1b];
+
+    /// Selector of `PSP37Batch::batch_transfer_from`.
+    const PSP37_BATCH_TRANSFER_FROM_SELECTOR: [u8; 4] = [0xf4, 0xeb, 0xee, 0xd2];
+
+    /// Error returned by a PSP22 token, mirrored so that it can be decoded and surfaced to callers.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub enum PSP22Error {
+        /// Custom error type for cases if writer of traits added own restrictions.
+        Custom(String),
+        /// Returned if not enough balance to fulfill a request is available.
+        InsufficientBalance,
+        /// Returned if not enough allowance to fulfill a request is available.
+        InsufficientAllowance,
+        /// Returned if recipient's address is zero.
+        ZeroRecipientAddress,
+        /// Returned if sender's address is zero.
+        ZeroSenderAddress,
+        /// Returned if safe transfer check fails.
+        SafeTransferCheckFailed(String),
+    }
+
+    /// Identifier of a PSP34 or PSP37 token, mirrored so that it can be passed to collections.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub enum Id {
+        U8(u8),
+        U16(u16),
+        U32(u32),
+        U64(u64),
+        U128(u128),
+        Bytes(Vec<u8>),
+    }
+
+    /// Error returned by a PSP34 collection, mirrored so that it can be decoded and surfaced to callers.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub enum PSP34Error {
+        /// Custom error type for cases if writer of traits added own restrictions.
+        Custom(String),
+        /// Returned if owner approves self.
+        SelfApprove,
+        /// Returned if the caller doesn't have allowance for transferring.
+        NotApproved,
+        /// Returned if the owner already own the token.
+        TokenExists,
+        /// Returned if the token doesn't exist.
+        TokenNotExists,
+        /// Returned if safe transfer check fails.
+        SafeTransferCheckFailed(String),
+    }
+
+    /// Error returned by a PSP37 collection, mirrored so that it can be decoded and surfaced to callers.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub enum PSP37Error {
+        /// Custom error type for cases if writer of traits added own restrictions.
+        Custom(String),
+        /// Returned if the account doesn't contain enough funds.
+        InsufficientBalance,
+        /// Returned if recipient is zero account.
+        TransferToZeroAddress,
+        /// Returned if the caller is not allowed.
+        NotAllowed,
+        /// Returned if safe transfer check fails.
+        SafeTransferCheckFailed(String),
+    }
+
+    /// Errors that can occur upon calling this contract.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub enum Error {
+        /// Returned if the caller is not authenticated by the registry.
+        NotAuthenticated,
+        /// Returned if the token could not be called.
+        TokenCallFailed,
+        /// Returned if the PSP22 token rejected the transfer.
+        PSP22(PSP22Error),
+        /// Returned if the token is not owned by the account it is transferred from.
+        NotTokenOwner,
+        /// Returned if the PSP34 collection rejected the transfer.
+        PSP34(PSP34Error),
+        /// Returned if the PSP37 collection rejected the transfer.
+        PSP37(PSP37Error),
+        /// Returned if the caller is not the owner of the proxy.
+        NotOwner,
+        /// Returned if no registry change is pending.
+        NoPendingRegistry,
+        /// Returned if the delay period of the pending registry change has not passed.
+        RegistryChangeDelayed,
+    }
+
+    /// Emitted when the owner schedules a registry change.
+    #[ink(event)]
+    pub struct RegistryChangeStarted {
+        #[ink(topic)]
+        registry: AccountId,
+        effective_time: Timestamp,
+    }
+
+    /// Emitted when a scheduled registry change takes effect.
+    #[ink(event)]
+    pub struct RegistryChanged {
+        #[ink(topic)]
+        registry: AccountId,
+    }
 
     /// Defines the storage of your contract.
     /// Add new fields to the below struct in order
     /// to add new static storage fields to your contract.
     #[ink(storage)]
     pub struct WyvernTokenTransferProxy {
-        // Authentication registry.
+        /// Owner of the proxy, allowed to change the registry.
+        owner: AccountId,
+        /// Authentication registry.
         registry: AccountId,
+        /// Registry scheduled to replace `registry`, and the time it was scheduled at.
+        pending_registry: Option<(AccountId, Timestamp)>,
     }
 
     impl WyvernTokenTransferProxy {
+        /// Create a transfer proxy authenticating its callers with `registry`, owned by the caller.
         #[ink(constructor)]
-        pub fn new() -> Self {
-            Self {}
+        pub fn new(registry: AccountId) -> Self {
+            Self {
+                owner: Self::env().caller(),
+                registry,
+                pending_registry: None,
+            }
         }
-    }
 
-    impl TokenTransferProxy for WyvernTokenTransferProxy {
-        /// Call ERC20 `transferFrom`
-        /// @dev Authenticated contract only
-        /// @param token ERC20 token address
-        /// @param from From address
-        /// @param to To address
-        /// @param amount Transfer amount
+        /// Authentication registry.
+        #[ink(message)]
+        pub fn registry(&self) -> AccountId {
+            self.registry
+        }
+
+        /// Registry scheduled to replace the current one, with the time it can be applied from.
+        #[ink(message)]
+        pub fn pending_registry(&self) -> Option<(AccountId, Timestamp)> {
+            self.pending_registry
+                .map(|(registry, start)| (registry, start.saturating_add(DELAY_PERIOD)))
+        }
+
+        /// Start the process to change the authentication registry. Subject to delay period.
+        ///dev Owner only; replaces any change already pending
+        ///param registry :AccountId of the new registry
+        #[ink(message)]
+        pub fn set_registry(&mut self, registry: AccountId) -> Result<(), Error> {
+            if self.env().caller() != self.owner {
+                return Err(Error::NotOwner)
+            }
+            let now = self.env().block_timestamp();
+            self.pending_registry = Some((registry, now));
+            self.env().emit_event(RegistryChangeStarted {
+                registry,
+                effective_time: now.saturating_add(DELAY_PERIOD),
+            });
+            Ok(())
+        }
+
+        /// End the process to change the authentication registry after the delay period has passed.
+        ///dev Owner only
+        #[ink(message)]
+        pub fn end_set_registry(&mut self) -> Result<(), Error> {
+            if self.env().caller() != self.owner {
+                return Err(Error::NotOwner)
+            }
+            let (registry, start) = self.pending_registry.ok_or(Error::NoPendingRegistry)?;
+            if start.saturating_add(DELAY_PERIOD) > self.env().block_timestamp() {
+                return Err(Error::RegistryChangeDelayed)
+            }
+            self.registry = registry;
+            self.pending_registry = None;
+            self.env().emit_event(RegistryChanged { registry });
+            Ok(())
+        }
+
+        /// Call PSP22 `transfer_from`
+        ///dev Authenticated contract only
+        ///param token PSP22 token address
+        ///param from From address
+        ///param to To address
+        ///param amount Transfer amount
         #[ink(message)]
         pub fn transfer_from(
+            &mut self,
+            token: AccountId,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+        ) -> Result<(), Error> {
+            self.ensure_caller_is_authenticated()?;
+            self.psp22_transfer_from(token, from, to, amount)
+        }
+
+        /// Call PSP22 `transfer_from` for each entry, reverting all transfers if any fails
+        ///dev Authenticated contract only
+        ///param transfers (token, from, to, amount) of each transfer
+        #[ink(message)]
+        pub fn batch_transfer_from(
+            &mut self,
+            transfers: Vec<(AccountId, AccountId, AccountId, Balance)>,
+        ) -> Result<(), Error> {
+            self.ensure_caller_is_authenticated()?;
+            for (token, from, to, amount) in transfers {
+                self.psp22_transfer_from(token, from, to, amount)?;
+            }
+            Ok(())
+        }
+
+        /// Dry-run `batch_transfer_from`, returning the result of every entry
+        ///dev Always reverts, so that it is only useful as an RPC call; entries are attempted even if earlier ones fail
+        ///param transfers (token, from, to, amount) of each transfer
+        #[ink(message)]
+        pub fn eval_batch_transfer_from(
+            &mut self,
+            transfers: Vec<(AccountId, AccountId, AccountId, Balance)>,
+        ) -> Vec<Result<(), Error>> {
+            let results: Vec<Result<(), Error>> = match self.ensure_caller_is_authenticated() {
+                Ok(()) => {
+                    transfers
+                        .into_iter()
+                        .map(|(token, from, to, amount)| {
+                            self.psp22_transfer_from(token, from, to, amount)
+                        })
+                        .collect()
+                }
+                Err(_) => transfers.iter().map(|_| Err(Error::NotAuthenticated)).collect(),
+            };
+            ink_env::return_value::<Vec<Result<(), Error>>>(
+                ReturnFlags::default().set_reverted(true),
+                &results,
+            )
+        }
+
+        /// Call PSP34 `transfer`, with this proxy as the approved operator of `from`
+        ///dev Authenticated contract only
+        ///param collection PSP34 collection address
+        ///param from Owner of the token
+        ///param to To address
+        ///param id Token id
+        #[ink(message)]
+        pub fn transfer_nft_from(
+            &mut self,
+            collection: AccountId,
+            from: AccountId,
+            to: AccountId,
+            id: Id,
+        ) -> Result<(), Error> {
+            self.ensure_caller_is_authenticated()?;
+
+            // PSP34 `transfer` moves the token from its owner, so assert it belongs to `from`.
+            let owner = build_call::<<Self as ::ink_lang::reflect::ContractEnv>::Env>()
+                .call_type(
+                    Call::new()
+                        .callee(collection)
+                        .gas_limit(0)
+                        .transferred_value(0),
+                )
+                .exec_input(ExecutionInput::new(PSP34_OWNER_OF_SELECTOR.into()).push_arg(id.clone()))
+                .returns::<Option<AccountId>>()
+                .fire()
+                .map_err(|_| Error::TokenCallFailed)?;
+            if owner != Some(from) {
+                return Err(Error::NotTokenOwner)
+            }
+
+            build_call::<<Self as ::ink_lang::reflect::ContractEnv>::Env>()
+                .call_type(
+                    Call::new()
+                        .callee(collection)
+                        .gas_limit(0)
+                        .transferred_value(0),
+                )
+                .exec_input(
+                    ExecutionInput::new(PSP34_TRANSFER_SELECTOR.into())
+                        .push_arg(to)
+                        .push_arg(id)
+                        .push_arg(Vec::<u8>::new()),
+                )
+                .returns::<Result<(), PSP34Error>>()
+                .fire()
+                .map_err(|_| Error::TokenCallFailed)?
+                .map_err(Error::PSP34)
+        }
+
+        /// Call PSP37 `batch_transfer_from`, moving several token ids of one collection in a single call
+        ///dev Authenticated contract only
+        ///param token PSP37 collection address
+        ///param from From address
+        ///param to To address
+        ///param ids_amounts Token ids and the amount of each to transfer
+        ///param data Additional data forwarded to the collection
+        #[ink(message)]
+        pub fn transfer_multi_from(
+            &mut self,
+            token: AccountId,
+            from: AccountId,
+            to: AccountId,
+            ids_amounts: Vec<(Id, Balance)>,
+            data: Vec<u8>,
+        ) -> Result<(), Error> {
+            self.ensure_caller_is_authenticated()?;
+            build_call::<<Self as ::ink_lang::reflect::ContractEnv>::Env>()
+                .call_type(Call::new().callee(token).gas_limit(0).transferred_value(0))
+                .exec_input(
+                    ExecutionInput::new(PSP37_BATCH_TRANSFER_FROM_SELECTOR.into())
+                        .push_arg(from)
+                        .push_arg(to)
+                        .push_arg(ids_amounts)
+                        .push_arg(data),
+                )
+                .returns::<Result<(), PSP37Error>>()
+                .fire()
+                .map_err(|_| Error::TokenCallFailed)?
+                .map_err(Error::PSP37)
+        }
+
+        /// Call PSP22 `transfer_from` on `token`, with this proxy as the spender.
+        fn psp22_transfer_from(
+            &self,
             token: AccountId,
             from: AccountId,
             to: AccountId,
             amount: Balance,
-        ) -> bool {
-            // require(registry.contracts(self.env().caller()));
-            // return ERC20(token).transferFrom(from, to, amount);
- let transferred_value= Balance::default();
-                let gas_limit= 0;
-for (i, &callee) in callees.iter().enumerate() {
-                let result  = build_call::<<Self as ::ink_lang::reflect::ContractEnv>::Env>()
-                       .call_type(
-                    Call::new().callee(callee)
-                    .gas_limit(gas_limit)
-                    .transferred_value(transferred_value)
-                    ).exec_input(
-                        ExecutionInput::new(selector.into())
-                            .push_arg(from)
-                            .push_arg(to)
-                            .push_arg(values[i]),
-                    )
-                    .returns::<Vec<u8>>()
-                    .fire()
-                    .map_err(|_| Error::TransactionFailed);
-
-            for (i, &callee) in callees.iter().enumerate() {
-                let result  = build_call::<<Self as ::ink_lang::reflect::ContractEnv>::Env>()
-                       .call_type(
-                    Call::new().callee(callee)
-                    .gas_limit(gas_limit)
-                    .transferred_value(transferred_value)
-                    ).exec_input(
-                        ExecutionInput::new(selector.into())
-                            .push_arg(from)
-                            .push_arg(to)
-                            .push_arg(values[i]),
-                    )
-                    .returns::<()>()
-                    .fire()
-                    .map_err(|_| Error::TransactionFailed);
+        ) -> Result<(), Error> {
+            build_call::<<Self as ::ink_lang::reflect::ContractEnv>::Env>()
+                .call_type(Call::new().callee(token).gas_limit(0).transferred_value(0))
+                .exec_input(
+                    ExecutionInput::new(PSP22_TRANSFER_FROM_SELECTOR.into())
+                        .push_arg(from)
+                        .push_arg(to)
+                        .push_arg(amount)
+                        .push_arg(Vec::<u8>::new()),
+                )
+                .returns::<Result<(), PSP22Error>>()
+                .fire()
+                .map_err(|_| Error::TokenCallFailed)?
+                .map_err(Error::PSP22)
+        }
+
+        /// Return an error if the caller is not a contract authenticated by the registry.
+        fn ensure_caller_is_authenticated(&self) -> Result<(), Error> {
+            let authenticated = build_call::<<Self as ::ink_lang::reflect::ContractEnv>::Env>()
+                .call_type(Call::new().callee(self.registry).gas_limit(0).transferred_value(0))
+                .exec_input(
+                    ExecutionInput::new(REGISTRY_CONTRACTS_SELECTOR.into()).push_arg(self.env().caller()),
+                )
+                .returns::<bool>()
+                .fire()
+                .map_err(|_| Error::NotAuthenticated)?;
+            if !authenticated {
+                return Err(Error::NotAuthenticated)
+            }
+            Ok(())
         }
     }
 
@@ -83,21 +415,147 @@ for (i, &callee) in callees.iter().enumerate() {
 
         /// Imports `ink_lang` so we can use `#[ink::test]`.
         use ink_lang as ink;
+        use scale::{
+            Decode,
+            Encode,
+        };
+
+        /// Selector ink! derives for the trait message `name`: the first four bytes of its BLAKE2b-256 hash.
+        fn selector(name: &str) -> [u8; 4] {
+            let mut output = [0u8; 32];
+            ink_env::hash_bytes::<ink_env::hash::Blake2x256>(name.as_bytes(), &mut output);
+            [output[0], output[1], output[2], output[3]]
+        }
+
+        #[test]
+        fn psp22_selector_matches_trait_message() {
+            assert_eq!(PSP22_TRANSFER_FROM_SELECTOR, selector("PSP22::transfer_from"));
+        }
 
-        /// We test if the default constructor does its job.
-        // #[ink::test]
-        // fn default_works() {
-        //     let wyvern_token_transfer_proxy = WyvernTokenTransferProxy::default();
-        //     assert_eq!(wyvern_token_transfer_proxy.get(), false);
-        // }
-
-        // /// We test a simple use case of our contract.
-        // #[ink::test]
-        // fn it_works() {
-        //     let mut wyvern_token_transfer_proxy = WyvernTokenTransferProxy::new(false);
-        //     assert_eq!(wyvern_token_transfer_proxy.get(), false);
-        //     wyvern_token_transfer_proxy.flip();
-        //     assert_eq!(wyvern_token_transfer_proxy.get(), true);
-        // }
+        #[test]
+        fn psp22_error_decodes_token_encoding() {
+            // Variant indices must follow the PSP22 standard's declaration order.
+            assert_eq!(PSP22Error::Custom(String::from("paused")).encode()[0], 0);
+            assert_eq!(PSP22Error::InsufficientBalance.encode(), [1]);
+            assert_eq!(PSP22Error::InsufficientAllowance.encode(), [2]);
+            assert_eq!(PSP22Error::ZeroRecipientAddress.encode(), [3]);
+            assert_eq!(PSP22Error::ZeroSenderAddress.encode(), [4]);
+            assert_eq!(PSP22Error::SafeTransferCheckFailed(String::new()).encode()[0], 5);
+
+            let returned: Result<(), PSP22Error> = Err(PSP22Error::Custom(String::from("paused")));
+            assert_eq!(
+                Result::<(), PSP22Error>::decode(&mut &returned.encode()[..]).ok(),
+                Some(returned)
+            );
+        }
+
+        #[test]
+        fn psp34_selectors_match_trait_messages() {
+            assert_eq!(PSP34_OWNER_OF_SELECTOR, selector("PSP34::owner_of"));
+            assert_eq!(PSP34_TRANSFER_SELECTOR, selector("PSP34::transfer"));
+        }
+
+        #[test]
+        fn id_encodes_like_collection_id() {
+            assert_eq!(Id::U8(7).encode(), [0, 7]);
+            assert_eq!(Id::U16(7).encode(), [1, 7, 0]);
+            assert_eq!(Id::U32(7).encode(), [2, 7, 0, 0, 0]);
+            assert_eq!(Id::U64(7).encode()[0], 3);
+            assert_eq!(Id::U128(7).encode()[0], 4);
+            assert_eq!(Id::Bytes(vec![7, 8]).encode(), [5, 8, 7, 8]);
+        }
+
+        #[test]
+        fn psp34_error_decodes_collection_encoding() {
+            // Variant indices must follow the PSP34 standard's declaration order.
+            assert_eq!(PSP34Error::Custom(String::from("paused")).encode()[0], 0);
+            assert_eq!(PSP34Error::SelfApprove.encode(), [1]);
+            assert_eq!(PSP34Error::NotApproved.encode(), [2]);
+            assert_eq!(PSP34Error::TokenExists.encode(), [3]);
+            assert_eq!(PSP34Error::TokenNotExists.encode(), [4]);
+            assert_eq!(PSP34Error::SafeTransferCheckFailed(String::new()).encode()[0], 5);
+        }
+
+        #[test]
+        fn psp37_selector_matches_trait_message() {
+            // `batch_transfer_from` belongs to the PSP37Batch extension, not to PSP37 itself.
+            assert_eq!(PSP37_BATCH_TRANSFER_FROM_SELECTOR, selector("PSP37Batch::batch_transfer_from"));
+            assert_ne!(PSP37_BATCH_TRANSFER_FROM_SELECTOR, selector("PSP37::batch_transfer_from"));
+        }
+
+        #[test]
+        fn psp37_error_decodes_collection_encoding() {
+            // Variant indices must follow the PSP37 standard's declaration order.
+            assert_eq!(PSP37Error::Custom(String::from("paused")).encode()[0], 0);
+            assert_eq!(PSP37Error::InsufficientBalance.encode(), [1]);
+            assert_eq!(PSP37Error::TransferToZeroAddress.encode(), [2]);
+            assert_eq!(PSP37Error::NotAllowed.encode(), [3]);
+            assert_eq!(PSP37Error::SafeTransferCheckFailed(String::new()).encode()[0], 4);
+        }
+
+        fn default_accounts() -> ink_env::test::DefaultAccounts<Environment> {
+            ink_env::test::default_accounts::<Environment>()
+        }
+
+        fn set_caller(caller: AccountId) {
+            ink_env::test::set_caller::<Environment>(caller);
+        }
+
+        /// Time between two blocks of the off-chain environment, which starts at timestamp 0.
+        const BLOCK_TIME: Timestamp = 6;
+
+        /// Advance blocks until the block timestamp is at least `now`, returning the timestamp reached.
+        fn advance_to(now: Timestamp) -> Timestamp {
+            let mut timestamp = ink_env::block_timestamp::<Environment>();
+            while timestamp < now {
+                ink_env::test::advance_block::<Environment>();
+                timestamp += BLOCK_TIME;
+            }
+            timestamp
+        }
+
+        #[ink::test]
+        fn registry_change_is_owner_only() {
+            let accounts = default_accounts();
+            set_caller(accounts.alice);
+            let mut proxy = WyvernTokenTransferProxy::new(accounts.django);
+
+            set_caller(accounts.bob);
+            assert_eq!(proxy.set_registry(accounts.frank), Err(Error::NotOwner));
+            assert_eq!(proxy.end_set_registry(), Err(Error::NotOwner));
+            assert_eq!(proxy.pending_registry(), None);
+        }
+
+        #[ink::test]
+        fn registry_change_must_be_started() {
+            let accounts = default_accounts();
+            set_caller(accounts.alice);
+            let mut proxy = WyvernTokenTransferProxy::new(accounts.django);
+
+            assert_eq!(proxy.end_set_registry(), Err(Error::NoPendingRegistry));
+        }
+
+        #[ink::test]
+        fn registry_change_waits_for_delay_period() {
+            let accounts = default_accounts();
+            set_caller(accounts.alice);
+            let mut proxy = WyvernTokenTransferProxy::new(accounts.django);
+
+            let start = advance_to(1_000);
+            assert_eq!(proxy.set_registry(accounts.frank), Ok(()));
+            assert_eq!(proxy.pending_registry(), Some((accounts.frank, start + DELAY_PERIOD)));
+
+            // The last block before the delay period has passed.
+            advance_to(start + DELAY_PERIOD - BLOCK_TIME);
+            assert_eq!(proxy.end_set_registry(), Err(Error::RegistryChangeDelayed));
+            assert_eq!(proxy.registry(), accounts.django);
+
+            assert_eq!(advance_to(start + DELAY_PERIOD), start + DELAY_PERIOD);
+            assert_eq!(proxy.end_set_registry(), Ok(()));
+            assert_eq!(proxy.registry(), accounts.frank);
+            assert_eq!(proxy.pending_registry(), None);
+            assert_eq!(proxy.end_set_registry(), Err(Error::NoPendingRegistry));
+            assert_eq!(ink_env::test::recorded_events().count(), 2);
+        }
     }
 }